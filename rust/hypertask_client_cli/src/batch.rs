@@ -0,0 +1,162 @@
+use crate::parse_args::{parse_cli_args, DRY_RUN_FLAG, FORCE_FLAG};
+use crate::working_set::WorkingSet;
+use hypertask_engine::prelude::*;
+use std::io::{self, BufRead};
+
+/// Splits a line into words the way a shell would, so that arguments with spaces can be
+/// quoted: words are split on whitespace, `'...'` keeps everything inside it as it is, `"..."`
+/// does too apart from `\"` and `\\`, a `\` outside of quotes keeps the next character as it is,
+/// and a `#` at the start of a word comments out the rest of the line
+fn split_words(line: &str) -> Result<Vec<String>, &'static str> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(word) = word.take() {
+                    words.push(word);
+                }
+            }
+            '#' if word.is_none() => break,
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("`'` is missing a matching `'`"),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ '"') | Some(c @ '\\') => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("`\"` is missing a matching `\"`"),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("`\"` is missing a matching `\"`"),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err("`\\` at the end of the line has nothing to escape"),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if let Some(word) = word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+/// Reads a command from each line of stdin, written as it would be after `task` in a shell,
+/// eg: `add "buy milk" +shopping`. Blank lines, and lines starting with `#`, are skipped, and
+/// `--force` or `--dry-run` on a line are rejected
+pub fn read_batch_commands(
+    attribute_schema: &AttributeSchema,
+    working_set: &WorkingSet,
+) -> HyperTaskResult<Vec<Command>> {
+    let stdin = io::stdin();
+    let mut commands = vec![];
+
+    for (index, line) in stdin.lock().lines().enumerate() {
+        let line_error = || {
+            HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                .with_msg(|| format!("could not parse line {} of the batch", index + 1))
+        };
+
+        let line = line.map_err(|e| {
+            HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Read)
+                .msg("could not read batch commands")
+                .from(e)
+        })?;
+
+        let tokens = split_words(&line).map_err(|msg| {
+            line_error().from(
+                HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                    .msg(msg),
+            )
+        })?;
+
+        if tokens.is_empty() {
+            continue;
+        }
+
+        // these apply to the whole batch, so they're only read from the arguments to `task`
+        if let Some(flag) = tokens
+            .iter()
+            .find(|token| *token == FORCE_FLAG || *token == DRY_RUN_FLAG)
+        {
+            return Err(line_error().from(
+                HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                    .with_msg(|| format!("`{}` must be passed to `task batch`, not a line", flag)),
+            ));
+        }
+
+        commands.push(
+            parse_cli_args(tokens.iter(), attribute_schema, working_set)
+                .map_err(|e| line_error().from(e))?,
+        );
+    }
+
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_whitespace_outside_of_quotes() {
+        assert_eq!(
+            split_words(r#"add "buy milk" +shopping 'due:next week'"#),
+            Ok(vec![
+                "add".to_owned(),
+                "buy milk".to_owned(),
+                "+shopping".to_owned(),
+                "due:next week".to_owned(),
+            ])
+        );
+        assert_eq!(
+            split_words(r#"add say\ \"hi\" "a \"quote\"" it\'s"#),
+            Ok(vec![
+                "add".to_owned(),
+                "say \"hi\"".to_owned(),
+                "a \"quote\"".to_owned(),
+                "it's".to_owned(),
+            ])
+        );
+    }
+
+    #[test]
+    fn skips_comments() {
+        assert_eq!(split_words("# add nothing"), Ok(vec![]));
+        assert_eq!(
+            split_words("add tag#1 # the rest"),
+            Ok(vec!["add".to_owned(), "tag#1".to_owned()])
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_quotes() {
+        assert!(split_words("add \"buy milk").is_err());
+        assert!(split_words("add 'buy milk").is_err());
+        assert!(split_words("add milk\\").is_err());
+    }
+}
@@ -1,7 +1,9 @@
 use crate::render::RenderColumns;
 use hypertask_config_file_opener::ShellExpand;
 use hypertask_engine::prelude::*;
-use hypertask_task_io_operations::ProvidesDataDir;
+use hypertask_task_io_operations::{
+    ProvidesDataDir, DEFAULT_JOURNAL_LENGTH, DEFAULT_TOMBSTONE_RETENTION_DAYS,
+};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -23,6 +25,7 @@ impl Default for RenderConfig {
         Self {
             score_precision: 3,
             columns: vec![
+                RenderColumns::Number,
                 RenderColumns::Id,
                 RenderColumns::Score,
                 RenderColumns::Description,
@@ -53,19 +56,52 @@ impl ScoreCalculatorConfig {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CliConfig {
     pub task_state_dir: PathBuf,
+    pub tombstone_retention_days: Option<i64>,
+    pub journal_length: Option<usize>,
+    pub clear_completed_blockers: Option<bool>,
+    pub spawn_recurring_instances: Option<bool>,
+    #[serde(default)]
+    pub attributes: AttributeSchema,
     pub hooks: Option<HooksConfig>,
     pub render: RenderConfig,
     pub filter_calculator: ScoreCalculatorConfig,
     pub score_calculator: ScoreCalculatorConfig,
 }
 
+impl Default for CliConfig {
+    fn default() -> Self {
+        Self {
+            task_state_dir: PathBuf::default(),
+            tombstone_retention_days: None,
+            journal_length: None,
+            clear_completed_blockers: None,
+            spawn_recurring_instances: None,
+            attributes: AttributeSchema::default(),
+            hooks: None,
+            render: RenderConfig::default(),
+            // only show tasks that are pending or blocked
+            filter_calculator: ScoreCalculatorConfig::Single("waiting $ status : <".to_string()),
+            score_calculator: ScoreCalculatorConfig::default(),
+        }
+    }
+}
+
 impl ProvidesDataDir for CliConfig {
     fn get_task_state_dir(&self) -> &PathBuf {
         &self.task_state_dir
     }
+
+    fn get_tombstone_retention_days(&self) -> i64 {
+        self.tombstone_retention_days
+            .unwrap_or(DEFAULT_TOMBSTONE_RETENTION_DAYS)
+    }
+
+    fn get_journal_length(&self) -> usize {
+        self.journal_length.unwrap_or(DEFAULT_JOURNAL_LENGTH)
+    }
 }
 
 impl ShellExpand for CliConfig {
@@ -0,0 +1,296 @@
+use crate::config::CliConfig;
+use crate::write::write_engine_output;
+use chrono::prelude::*;
+use hypertask_engine::prelude::*;
+use hypertask_task_io_operations::get_task;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command as ProcessCommand;
+use std::rc::Rc;
+
+const DEFAULT_EDITOR: &str = "vi";
+const ERROR_PREFIX: &str = "# error: ";
+
+fn edit_error(action: HyperTaskErrorAction, msg: &'static str) -> HyperTaskError {
+    HyperTaskError::new(HyperTaskErrorDomain::Input, action).msg(msg)
+}
+
+/// Writes the task as toml, tables have to come after plain values in toml, so the task is
+/// converted to a `toml::Value` first, which orders its keys that way
+fn task_to_document(task: &Task) -> HyperTaskResult<String> {
+    let value = toml::Value::try_from(task).map_err(|e| {
+        edit_error(
+            HyperTaskErrorAction::Write,
+            "could not convert task to toml",
+        )
+        .from(e)
+    })?;
+
+    let body = toml::to_string_pretty(&value)
+        .map_err(|e| edit_error(HyperTaskErrorAction::Write, "could not serialize task").from(e))?;
+
+    Ok(format!(
+        "# editing task `{}`, save and quit to apply the changes, or empty the file to cancel\n# `updated_at` is set when the task is saved\n\n{}",
+        task.get_id(),
+        body
+    ))
+}
+
+/// Parses an edited document back into a task, `None` means the edit was cancelled
+fn document_to_task(document: &str, original: &Task) -> Result<Option<Task>, String> {
+    if document.lines().all(|line| {
+        let line = line.trim();
+        line.is_empty() || line.starts_with('#')
+    }) {
+        return Ok(None);
+    }
+
+    let value: toml::Value = toml::from_str(document).map_err(|e| e.to_string())?;
+    let task: Task = value.clone().try_into().map_err(|e| e.to_string())?;
+
+    if task.get_id() != original.get_id() {
+        return Err(format!(
+            "the id can't be changed, it should be `{}`",
+            original.get_id()
+        ));
+    }
+
+    // fields that aren't part of a task are dropped when it's parsed, which would silently
+    // lose a misspelt field, empty lists are dropped too but they don't hold anything
+    let known_keys: BTreeSet<String> = match toml::Value::try_from(&task) {
+        Ok(toml::Value::Table(table)) => table.keys().cloned().collect(),
+        _ => BTreeSet::new(),
+    };
+    if let toml::Value::Table(table) = &value {
+        let unknown_keys: Vec<&str> = table
+            .iter()
+            .filter(|(key, value)| {
+                let is_empty = match value {
+                    toml::Value::Array(array) => array.is_empty(),
+                    toml::Value::Table(table) => table.is_empty(),
+                    _ => false,
+                };
+
+                !is_empty && !known_keys.contains(*key)
+            })
+            .map(|(key, _)| key.as_str())
+            .collect();
+
+        if !unknown_keys.is_empty() {
+            return Err(format!(
+                "tasks don't have the fields: {}",
+                unknown_keys.join(" ")
+            ));
+        }
+    }
+
+    Ok(Some(task))
+}
+
+fn open_editor(path: &Path) -> HyperTaskResult<()> {
+    let editor = env::var("EDITOR").unwrap_or_else(|_| DEFAULT_EDITOR.to_string());
+
+    // run through the shell, so that `$EDITOR` can include arguments like `code --wait`
+    let status = ProcessCommand::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status()
+        .map_err(|e| {
+            HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Run)
+                .with_msg(|| format!("could not run the editor `{}`", editor))
+                .from(e)
+        })?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(
+            HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Run)
+                .with_msg(|| format!("the editor `{}` exited with {}", editor, status)),
+        )
+    }
+}
+
+/// Creates the file that the task is edited in, with a name that can't be guessed, and fails
+/// rather than following anything already at that path, as the temp dir is shared with other users
+fn create_document_file(id: &Id, document: &str) -> HyperTaskResult<PathBuf> {
+    let suffix: String = thread_rng().sample_iter(&Alphanumeric).take(12).collect();
+    let path = env::temp_dir().join(format!("hypertask-{}-{}.toml", id, suffix));
+
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(document.as_bytes()))
+        .map_err(|e| {
+            edit_error(
+                HyperTaskErrorAction::Write,
+                "could not create a file to edit the task in",
+            )
+            .from(e)
+        })?;
+
+    Ok(path)
+}
+
+/// Opens the task in `$EDITOR` until it's saved as a valid task, or the edit is cancelled
+fn edit_task(original: &Task) -> HyperTaskResult<Option<Task>> {
+    let path = create_document_file(&original.get_id(), &task_to_document(original)?)?;
+
+    let result = edit_document(original, &path);
+
+    fs::remove_file(&path).ok();
+
+    result
+}
+
+fn edit_document(original: &Task, path: &Path) -> HyperTaskResult<Option<Task>> {
+    loop {
+        open_editor(path)?;
+
+        let edited = fs::read_to_string(path).map_err(|e| {
+            edit_error(HyperTaskErrorAction::Read, "could not read the edited task").from(e)
+        })?;
+
+        match document_to_task(&edited, original) {
+            Ok(task) => return Ok(task),
+            // go back to the editor, with the error at the top of what was written
+            Err(msg) => {
+                let without_old_errors: Vec<&str> = edited
+                    .lines()
+                    .filter(|line| !line.starts_with(ERROR_PREFIX))
+                    .collect();
+
+                let document = format!(
+                    "{}\n{}\n",
+                    msg.lines()
+                        .map(|line| format!("{}{}", ERROR_PREFIX, line))
+                        .collect::<Vec<String>>()
+                        .join("\n"),
+                    without_old_errors.join("\n")
+                );
+
+                // the file is ours, it was created when editing started, so it's only truncated
+                OpenOptions::new()
+                    .write(true)
+                    .truncate(true)
+                    .open(path)
+                    .and_then(|mut file| file.write_all(document.as_bytes()))
+                    .map_err(|e| {
+                        edit_error(
+                            HyperTaskErrorAction::Write,
+                            "could not write the task to edit",
+                        )
+                        .from(e)
+                    })?;
+            }
+        }
+    }
+}
+
+/// Edits the one task that matched the query as a document in `$EDITOR`, then runs the edited
+/// task through the engine, so that it's checked and written like any other change
+pub fn run_edit(
+    cli_config: &CliConfig,
+    engine: &mut Engine,
+    engine_output: &EngineOutput,
+    original_tasks: &HashMap<Rc<Id>, Rc<Task>>,
+    command: &str,
+    now: &DateTime<Utc>,
+) -> HyperTaskResult<()> {
+    let original = match engine_output.display_tasks.as_slice() {
+        [(_, _, task)] => task,
+        [] => return Err(edit_error(HyperTaskErrorAction::Read, "no tasks matched")),
+        _ => {
+            return Err(edit_error(
+                HyperTaskErrorAction::Read,
+                "more than one task matched, only one task can be edited at a time",
+            ))
+        }
+    };
+
+    let edited = match edit_task(original)? {
+        Some(edited) if edited != **original => edited.to_restored(now),
+        Some(_) => {
+            println!("no changes made to `{}`", original.get_id());
+            return Ok(());
+        }
+        None => {
+            println!("cancelled editing `{}`", original.get_id());
+            return Ok(());
+        }
+    };
+
+    // the task can be changed by another command, or a sync, while it's open in the editor,
+    // writing the edit over that would silently lose the change
+    match get_task(cli_config, &original.get_id())? {
+        Some(stored) if stored == **original => {}
+        Some(ref stored) if !stored.is_tombstone() => return Err(edit_error(
+            HyperTaskErrorAction::Write,
+            "the task was changed while it was being edited, edit it again to keep both changes",
+        )),
+        _ => {
+            return Err(edit_error(
+                HyperTaskErrorAction::Write,
+                "the task was deleted while it was being edited",
+            ))
+        }
+    }
+
+    let engine_output = engine.run(Command::Put(Box::new(edited)))?;
+    write_engine_output(cli_config, &engine_output, original_tasks, command, now)?;
+
+    println!("updated `{}`", original.get_id());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_task() -> Task {
+        serde_json::from_str(
+            r#"{
+                "created_at": "2015-11-15T09:10:11Z",
+                "updated_at": "2015-11-15T09:10:11Z",
+                "id": "aaaa",
+                "description": "write report",
+                "tags": ["work"],
+                "annotations": [{ "created_at": "2015-11-15T09:10:11Z", "text": "draft" }]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn round_trips_a_task_through_a_document() {
+        let task = create_task();
+        let document = task_to_document(&task).unwrap();
+
+        assert_eq!(document_to_task(&document, &task), Ok(Some(task)));
+    }
+
+    #[test]
+    fn rejects_changed_ids_and_unknown_fields() {
+        let task = create_task();
+        let document = task_to_document(&task).unwrap();
+
+        assert!(document_to_task(&document.replace("aaaa", "bbbb"), &task).is_err());
+        assert!(document_to_task(&format!("dew = \"tomorrow\"\n{}", document), &task).is_err());
+    }
+
+    #[test]
+    fn cancels_when_the_document_is_emptied() {
+        let task = create_task();
+
+        assert_eq!(document_to_task("# nothing here\n\n", &task), Ok(None));
+    }
+}
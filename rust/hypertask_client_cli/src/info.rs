@@ -0,0 +1,198 @@
+use crate::render::{format_date_time, format_time_spent};
+use ansi_term::Style;
+use chrono::prelude::*;
+use hypertask_engine::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+fn print_field(name: &str, value: impl ToString) {
+    println!(
+        "{}{}",
+        Style::new().bold().paint(format!("{:<14}", name)),
+        value.to_string()
+    );
+}
+
+fn describe(id: &Id, tasks: &HashMap<Rc<Id>, Rc<Task>>) -> String {
+    match tasks
+        .get(id)
+        .and_then(|task| task.get_description().as_ref())
+    {
+        Some(description) => format!("{} {}", id, description),
+        None => id.to_string(),
+    }
+}
+
+/// Prints every task that `task` is waiting on, the blockers of each blocker are indented
+/// beneath it
+fn print_blocker_chain(
+    task: &Task,
+    tasks: &HashMap<Rc<Id>, Rc<Task>>,
+    depth: usize,
+    seen: &mut HashSet<Rc<Id>>,
+) {
+    let mut blocked_by: Vec<&Rc<Id>> = task.get_blocked_by().iter().collect();
+    blocked_by.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for blocker_id in blocked_by {
+        let name = if depth == 0 { "blocked_by" } else { "" };
+        print_field(
+            name,
+            format!("{}{}", "  ".repeat(depth), describe(blocker_id, tasks)),
+        );
+
+        // blockers can't form cycles, but one task can block several others in the chain
+        if seen.insert(blocker_id.clone()) {
+            if let Some(blocker) = tasks.get(blocker_id) {
+                print_blocker_chain(blocker, tasks, depth + 1, seen);
+            }
+        }
+    }
+}
+
+fn print_task_info(
+    task: &Task,
+    score: Score,
+    filter: Score,
+    engine_output: &EngineOutput,
+    dependency_graph: &DependencyGraph,
+    tasks: &HashMap<Rc<Id>, Rc<Task>>,
+    now: &DateTime<Utc>,
+) {
+    let id = task.get_id();
+
+    print_field("id", &id);
+    print_field(
+        "description",
+        task.get_description().as_deref().unwrap_or_default(),
+    );
+    if let Some(status) = engine_output.statuses.get(&id) {
+        print_field("status", status);
+    }
+    print_field("score", format!("{:.4}", score));
+    print_field(
+        "filter",
+        format!(
+            "{:.4} ({})",
+            filter,
+            if filter > 0.0 {
+                "shown"
+            } else {
+                "filtered out"
+            }
+        ),
+    );
+    print_field("created_at", format_date_time(*task.get_created_at()));
+    print_field("updated_at", format_date_time(*task.get_updated_at()));
+
+    let dates = [
+        ("due", task.get_due()),
+        ("wait", task.get_wait()),
+        ("snooze", task.get_snooze()),
+        ("done", task.get_done()),
+    ];
+    for (name, date_time) in dates.iter() {
+        if let Some(date_time) = date_time {
+            print_field(name, format_date_time(*date_time));
+        }
+    }
+
+    if let Some(recur) = task.get_recur() {
+        print_field("recur", recur);
+    }
+    if let Some(template) = task.get_template() {
+        print_field("template", describe(template, tasks));
+    }
+    if let Some(parent) = task.get_parent() {
+        print_field("parent", describe(parent, tasks));
+    }
+
+    if !task.get_tags().is_empty() {
+        let mut tags: Vec<String> = task
+            .get_tags()
+            .iter()
+            .map(|tag| format!("+{}", tag))
+            .collect();
+        tags.sort();
+        print_field("tags", tags.join(" "));
+    }
+
+    for (name, value) in task.get_attributes() {
+        print_field(name, value);
+    }
+
+    if !task.get_intervals().is_empty() {
+        print_field(
+            "time_spent",
+            format!(
+                "{}{}",
+                format_time_spent(task.get_time_spent(now)),
+                if task.is_active() { " (active)" } else { "" }
+            ),
+        );
+    }
+
+    for (index, annotation) in task.get_annotations().iter().enumerate() {
+        print_field(if index == 0 { "annotations" } else { "" }, annotation);
+    }
+
+    print_blocker_chain(task, tasks, 0, &mut HashSet::new());
+
+    let mut dependants: Vec<Rc<Id>> = dependency_graph
+        .get_dependants(&id)
+        .iter()
+        .map(|dependant| dependant.get_id())
+        .collect();
+    dependants.sort_by(|a, b| a.0.cmp(&b.0));
+    for (index, dependant_id) in dependants.iter().enumerate() {
+        print_field(
+            if index == 0 { "blocks" } else { "" },
+            describe(dependant_id, tasks),
+        );
+    }
+
+    let mut children: Vec<Rc<Id>> = dependency_graph
+        .get_children(&id)
+        .iter()
+        .map(|child| child.get_id())
+        .collect();
+    children.sort_by(|a, b| a.0.cmp(&b.0));
+    for (index, child_id) in children.iter().enumerate() {
+        print_field(
+            if index == 0 { "children" } else { "" },
+            describe(child_id, tasks),
+        );
+    }
+}
+
+/// Prints every field of the tasks that matched the query, one task after another
+pub fn render_task_info(
+    engine_output: &EngineOutput,
+    engine: &mut Engine,
+    tasks: &HashMap<Rc<Id>, Rc<Task>>,
+    now: &DateTime<Utc>,
+) -> HyperTaskResult<()> {
+    if engine_output.display_tasks.is_empty() {
+        println!("no tasks matched");
+    }
+
+    for (index, (_, _, task)) in engine_output.display_tasks.iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+
+        let (score, filter) = engine.evaluate_task(task)?;
+
+        print_task_info(
+            task,
+            score,
+            filter,
+            engine_output,
+            engine.get_dependency_graph(),
+            tasks,
+            now,
+        );
+    }
+
+    Ok(())
+}
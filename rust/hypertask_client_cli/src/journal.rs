@@ -0,0 +1,91 @@
+use crate::config::CliConfig;
+use crate::write::{put_changed_tasks, run_after_hook};
+use ansi_term::Colour::Yellow;
+use chrono::prelude::*;
+use hypertask_engine::prelude::*;
+use hypertask_task_io_operations::{
+    get_journal_entries, get_latest_journal_entries, get_task, remove_latest_journal_entry,
+    JournalEntry,
+};
+
+/// Lists the commands that can be undone, `1` is the most recent
+pub fn run_history(cli_config: &CliConfig) -> HyperTaskResult<()> {
+    let entries = get_journal_entries(cli_config)?;
+
+    for (index, entry) in entries.iter().rev().enumerate() {
+        println!(
+            "{:>3} {} {} ({} tasks)",
+            index + 1,
+            entry
+                .created_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M"),
+            entry.command,
+            entry.changes.len()
+        );
+    }
+
+    Ok(())
+}
+
+fn undo_entry(
+    cli_config: &CliConfig,
+    entry: &JournalEntry,
+    now: &DateTime<Utc>,
+) -> HyperTaskResult<()> {
+    let mut restored_tasks = vec![];
+
+    for change in &entry.changes {
+        let current = get_task(cli_config, &change.after.get_id())?;
+
+        if current.as_ref() != Some(&change.after) {
+            eprintln!(
+                "{}",
+                Yellow.paint(format!(
+                    "warning: task `{}` has changed since `{}`, undoing anyway",
+                    change.after.get_id(),
+                    entry.command
+                ))
+            );
+        }
+
+        restored_tasks.push(match &change.before {
+            Some(task) => task.to_restored(now),
+            // the task was created by the command, so undoing it deletes it
+            None => change.after.to_tombstone(now),
+        });
+    }
+
+    put_changed_tasks(cli_config, restored_tasks.iter())
+}
+
+/// Restores the tasks changed by the most recent commands, `task undo 3` undoes the last three
+pub fn run_undo(
+    cli_config: &CliConfig,
+    args: &[String],
+    now: &DateTime<Utc>,
+) -> HyperTaskResult<()> {
+    let count = match args.first() {
+        Some(arg) => arg.parse::<usize>().map_err(|e| {
+            HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                .with_msg(|| format!("`{}` is not a number of commands to undo", arg))
+                .from(e)
+        })?,
+        None => 1,
+    };
+
+    // each entry is only removed from the journal once it's been undone, so that a command
+    // isn't lost if undoing it fails
+    for entry in get_latest_journal_entries(cli_config, count)? {
+        undo_entry(cli_config, &entry, now)?;
+        remove_latest_journal_entry(cli_config)?;
+
+        println!(
+            "undid `{}`, restoring {} tasks",
+            entry.command,
+            entry.changes.len()
+        );
+    }
+
+    run_after_hook(cli_config)
+}
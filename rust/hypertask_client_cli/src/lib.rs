@@ -7,22 +7,42 @@ extern crate hypertask_engine;
 extern crate render_simple_cli_table;
 extern crate shellexpand;
 
+mod batch;
 mod config;
+mod edit;
+mod info;
+mod journal;
 mod parse_args;
 mod render;
+mod timesheet;
+mod working_set;
+mod write;
 
+use crate::batch::read_batch_commands;
 use crate::config::CliConfig;
-use crate::parse_args::parse_cli_args;
-use crate::render::render_engine_output;
+use crate::edit::run_edit;
+use crate::info::render_task_info;
+use crate::journal::{run_history, run_undo};
+use crate::parse_args::{
+    get_cli_command, has_dry_run_flag, has_force_flag, parse_cli_args, CliCommand,
+};
+use crate::render::{render_engine_output, render_task_diffs};
+use crate::timesheet::run_timesheet;
+use crate::working_set::{get_working_set_entries, WorkingSet};
+use crate::write::write_engine_output;
+use ansi_term::Colour::Yellow;
 use chrono::prelude::*;
-use hypertask_config_file_opener::run_string_as_shell_command;
 use hypertask_config_file_opener::{ConfigFileGetter, ConfigFileOpener};
 use hypertask_engine::prelude::*;
-use hypertask_task_io_operations::{get_input_tasks, put_task};
+use hypertask_task_io_operations::{get_input_tasks, get_working_set, put_working_set};
 use std::collections::HashMap;
 use std::rc::Rc;
 
-fn create_stack_machine(now: &DateTime<Utc>, program: Vec<RPNSymbol>) -> StackMachine {
+fn create_stack_machine(
+    now: &DateTime<Utc>,
+    program: Vec<RPNSymbol>,
+    attribute_schema: &AttributeSchema,
+) -> StackMachine {
     let mut env = HashMap::new();
 
     env.insert("day_of_week", f64::from(now.weekday().number_from_monday()));
@@ -31,7 +51,7 @@ fn create_stack_machine(now: &DateTime<Utc>, program: Vec<RPNSymbol>) -> StackMa
     env.insert("month", f64::from(now.month()));
     env.insert("now", now.timestamp() as f64);
 
-    StackMachine::new(program, env)
+    StackMachine::new(program, env).with_attribute_schema(attribute_schema.clone())
 }
 
 pub fn run_cli(args: &[String]) -> HyperTaskResult<()> {
@@ -39,40 +59,97 @@ pub fn run_cli(args: &[String]) -> HyperTaskResult<()> {
     let config_file_getter: ConfigFileGetter<CliConfig> = config_file_opener.parse()?;
     let cli_config: &CliConfig = config_file_getter.get_config();
 
-    let tasks: HashMap<Rc<Id>, Rc<Task>> = get_input_tasks(&*cli_config)?;
     let now = Utc::now();
-    let score_machine = create_stack_machine(&now, cli_config.score_calculator.to_program());
-    let filter_machine = create_stack_machine(&now, cli_config.filter_calculator.to_program());
-
-    let mut engine: Engine = Engine::new(tasks, filter_machine, score_machine, now);
-
-    let EngineOutput {
-        mutated_tasks,
-        display_tasks,
-    } = engine.run(parse_cli_args(args.iter().skip(1))?)?;
-
-    if !mutated_tasks.is_empty() {
-        for task in mutated_tasks {
-            put_task(&*cli_config, &task)?;
-            if let Some(on_edit_cmd) = cli_config
-                .hooks
-                .as_ref()
-                .and_then(|config| config.on_edit.as_ref())
-            {
-                run_string_as_shell_command(&on_edit_cmd)?;
-            }
-        }
 
-        if let Some(after_cmd) = cli_config
-            .hooks
-            .as_ref()
-            .and_then(|config| config.after.as_ref())
-        {
-            print!("{}", run_string_as_shell_command(&after_cmd)?);
+    // these don't touch the engine, they work on the journal of earlier commands, or report on
+    // the tasks without changing them
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("history") => return run_history(cli_config),
+        Some("undo") => return run_undo(cli_config, &args[2..], &now),
+        Some("timesheet") => return run_timesheet(cli_config, &args[2..], &now),
+        _ => {}
+    }
+
+    let tasks: HashMap<Rc<Id>, Rc<Task>> = get_input_tasks(&*cli_config)?;
+    let original_tasks = tasks.clone();
+    let working_set = WorkingSet::new(get_working_set(cli_config)?, &tasks);
+    let score_machine = create_stack_machine(
+        &now,
+        cli_config.score_calculator.to_program(),
+        &cli_config.attributes,
+    );
+    let filter_machine = create_stack_machine(
+        &now,
+        cli_config.filter_calculator.to_program(),
+        &cli_config.attributes,
+    );
+
+    let mut engine: Engine = Engine::new(tasks, filter_machine, score_machine, now)
+        .with_clear_completed_blockers(cli_config.clear_completed_blockers.unwrap_or(false))
+        .with_spawn_recurring_instances(cli_config.spawn_recurring_instances.unwrap_or(false))
+        .with_force(has_force_flag(args));
+
+    // a single command is run as a batch of one
+    let commands = match args.get(1).map(|arg| arg.as_str()) {
+        Some("batch") => read_batch_commands(&cli_config.attributes, &working_set)?,
+        _ => vec![parse_cli_args(
+            args.iter().skip(1),
+            &cli_config.attributes,
+            &working_set,
+        )?],
+    };
+    // an empty batch lists nothing, so it mustn't replace the working set
+    let is_listing = !commands.is_empty()
+        && commands
+            .iter()
+            .all(|command| matches!(command, Command::Read(_)));
+
+    if has_dry_run_flag(args) {
+        render_task_diffs(&engine.preview(commands)?);
+        return Ok(());
+    }
+
+    // info and edit are reads of the tasks to show in detail, or to open in `$EDITOR`, so they
+    // don't renumber the tasks
+    match get_cli_command(args) {
+        Some(CliCommand::Info) => {
+            let engine_output = engine.run_batch(commands)?;
+            return render_task_info(&engine_output, &mut engine, &original_tasks, &now);
+        }
+        Some(CliCommand::Edit) => {
+            let engine_output = engine.run_batch(commands)?;
+            return run_edit(
+                cli_config,
+                &mut engine,
+                &engine_output,
+                &original_tasks,
+                &args[1..].join(" "),
+                &now,
+            );
         }
+        _ => {}
+    }
+
+    let engine_output = engine.run_batch(commands)?;
+
+    write_engine_output(
+        cli_config,
+        &engine_output,
+        &original_tasks,
+        &args[1..].join(" "),
+        &now,
+    )?;
+
+    // only listings renumber the tasks, so the numbers stay the same while acting on them
+    if is_listing {
+        put_working_set(cli_config, &get_working_set_entries(&engine_output))?;
     }
 
-    render_engine_output(display_tasks, &cli_config)?;
+    render_engine_output(&engine_output, &cli_config, &now, is_listing)?;
+
+    for warning in &engine_output.warnings {
+        eprintln!("{}", Yellow.paint(format!("warning: {}", warning)));
+    }
 
     Ok(())
 }
@@ -1,3 +1,7 @@
+mod query;
+
+use crate::parse_args::query::parse_as_query_expression;
+use crate::working_set::{parse_as_working_set_number, WorkingSet};
 use chrono::prelude::*;
 use chrono_english::{parse_date_string, Dialect};
 use hypertask_engine::prelude::*;
@@ -7,23 +11,58 @@ use time::Duration;
 #[derive(Debug)]
 pub enum CliCommand {
     Add,
+    Annotate,
     Delete,
+    Denotate,
     Done,
+    /// opens the matching task in `$EDITOR`
+    Edit,
+    /// shows every field of the matching tasks
+    Info,
     Modify,
     Snooze,
+    Start,
+    Stop,
 }
 
+/// Lets a parent be done while it still has open children
+pub const FORCE_FLAG: &str = "--force";
+
+pub fn has_force_flag(args: &[String]) -> bool {
+    args.iter().any(|arg| arg == FORCE_FLAG)
+}
+
+/// Shows what a command would change, without writing anything
+pub const DRY_RUN_FLAG: &str = "--dry-run";
+
+pub fn has_dry_run_flag(args: &[String]) -> bool {
+    args.iter().any(|arg| arg == DRY_RUN_FLAG)
+}
+
+const CREATE_ID_PREFIX: &str = "id:";
+
 pub fn parse_as_command(token: &str) -> Option<CliCommand> {
     match token {
         "add" => Some(CliCommand::Add),
+        "annotate" => Some(CliCommand::Annotate),
         "delete" => Some(CliCommand::Delete),
+        "denotate" => Some(CliCommand::Denotate),
         "done" => Some(CliCommand::Done),
+        "edit" => Some(CliCommand::Edit),
+        "info" => Some(CliCommand::Info),
         "modify" => Some(CliCommand::Modify),
         "snooze" => Some(CliCommand::Snooze),
+        "start" => Some(CliCommand::Start),
+        "stop" => Some(CliCommand::Stop),
         _ => None,
     }
 }
 
+/// Finds the command in the arguments, for commands that aren't run by the engine alone
+pub fn get_cli_command(args: &[String]) -> Option<CliCommand> {
+    partition_args(args.iter().skip(1)).1
+}
+
 fn partition_args<'a>(
     args: impl Iterator<Item = &'a String>,
 ) -> (Vec<&'a String>, Option<CliCommand>, Vec<&'a String>) {
@@ -32,7 +71,7 @@ fn partition_args<'a>(
 
     let mut command: Option<CliCommand> = None;
 
-    for arg in args {
+    for arg in args.filter(|arg| *arg != FORCE_FLAG && *arg != DRY_RUN_FLAG) {
         if command.is_none() {
             if let Some(c) = parse_as_command(&arg) {
                 command = Some(c);
@@ -55,6 +94,18 @@ pub fn parse_as_id(token: &str) -> Option<Id> {
     }
 }
 
+/// Parses a reference to another task, where bare numbers refer to the tasks in the last listing
+fn parse_as_task_reference(token: &str, working_set: &WorkingSet) -> HyperTaskResult<Id> {
+    if let Some(number) = parse_as_working_set_number(token) {
+        return working_set.get_id(number);
+    };
+
+    parse_as_id(token).ok_or_else(|| {
+        HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+            .with_msg(|| format!("`{}` is not a valid id", token))
+    })
+}
+
 pub fn parse_as_tag(token: &str) -> Option<Tag> {
     match (token.chars().nth(0), &token[1..]) {
         (Some('+'), name) => Some(Tag {
@@ -69,11 +120,235 @@ pub fn parse_as_tag(token: &str) -> Option<Tag> {
     }
 }
 
-pub fn parse_as_query(token: &str) -> HyperTaskResult<Query> {
+fn parse_as_attribute_name(name: &str) -> HyperTaskResult<QueryAttribute> {
+    match name {
+        "created_at" => Ok(QueryAttribute::CreatedAt),
+        "updated_at" => Ok(QueryAttribute::UpdatedAt),
+        "description" => Ok(QueryAttribute::Description),
+        "blocked" => Ok(QueryAttribute::Blocked),
+        "done" => Ok(QueryAttribute::Done),
+        "due" => Ok(QueryAttribute::Due),
+        "parent" => Ok(QueryAttribute::Parent),
+        "recur" => Ok(QueryAttribute::Recur),
+        "snooze" => Ok(QueryAttribute::Snooze),
+        "wait" => Ok(QueryAttribute::Wait),
+        _ => Err(
+            HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                .with_msg(|| format!("`{}` is not a valid attribute", name)),
+        ),
+    }
+}
+
+fn parse_as_date_attribute_name(name: &str) -> HyperTaskResult<QueryAttribute> {
+    let attribute = parse_as_attribute_name(name)?;
+
+    if attribute.is_date() {
+        Ok(attribute)
+    } else {
+        Err(
+            HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                .with_msg(|| format!("`{}` is not a date attribute", name)),
+        )
+    }
+}
+
+/// Durations are written as a number of minutes, hours, days or weeks, eg: `90min`, `2h`
+fn parse_as_duration(token: &str) -> HyperTaskResult<i64> {
+    let caps = DURATION_REGEX.captures(token).ok_or_else(|| {
+        HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+            .with_msg(|| format!("`{}` is not a valid duration", token))
+    })?;
+
+    let number = caps.get(1).unwrap().as_str().parse::<i64>().unwrap();
+
+    let seconds = match caps.get(2).unwrap().as_str() {
+        "min" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        u => panic!("{} is not a valid unit", u),
+    };
+
+    Ok(number * seconds)
+}
+
+fn parse_as_attribute_value(
+    name: &str,
+    attribute_type: &AttributeType,
+    value: &str,
+) -> HyperTaskResult<AttributeValue> {
+    match attribute_type {
+        AttributeType::String => Ok(AttributeValue::String(value.to_string())),
+        AttributeType::Number => value
+            .parse::<f64>()
+            .map(AttributeValue::Number)
+            .map_err(|e| {
+                HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                    .with_msg(|| format!("`{}` is not a valid number", value))
+                    .from(e)
+            }),
+        AttributeType::Date => parse_as_date_time(value).map(AttributeValue::Date),
+        AttributeType::Duration => parse_as_duration(value).map(AttributeValue::Duration),
+        AttributeType::Enum(values) => {
+            if values.iter().any(|v| v == value) {
+                Ok(AttributeValue::Enum(value.to_string()))
+            } else {
+                Err(
+                    HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                        .with_msg(|| {
+                            format!(
+                                "`{}` must be one of {}, not `{}`",
+                                name,
+                                values.join(", "),
+                                value
+                            )
+                        }),
+                )
+            }
+        }
+    }
+}
+
+/// Parses a value for a user defined attribute that is compared with `.below` or `.above`
+fn parse_as_ordered_attribute_value(
+    name: &str,
+    attribute_type: &AttributeType,
+    value: &str,
+) -> HyperTaskResult<AttributeValue> {
+    if attribute_type.is_ordered() {
+        parse_as_attribute_value(name, attribute_type, value)
+    } else {
+        Err(
+            HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                .with_msg(|| format!("`{}` can't be compared", name)),
+        )
+    }
+}
+
+fn parse_as_query_attribute(
+    token: &str,
+    attribute_schema: &AttributeSchema,
+) -> Option<HyperTaskResult<Query>> {
+    let colon_index = token.find(':')?;
+    let (key, value) = (&token[..colon_index], &token[colon_index + 1..]);
+
+    // keys can have a comparison attached, eg: `due.before`
+    let (name, comparison) = match key.find('.') {
+        Some(dot_index) => (&key[..dot_index], Some(&key[dot_index + 1..])),
+        None => (key, None),
+    };
+
+    Some(match (name, comparison, value) {
+        ("description", None, text) => Ok(Query::Description(text.to_string())),
+        ("word", None, word) => Ok(Query::Word(word.to_string())),
+
+        ("has", None, name) if attribute_schema.get(name).is_some() => {
+            Ok(Query::HasAttribute(name.to_string()))
+        }
+        ("has", None, name) => parse_as_attribute_name(name).map(Query::Has),
+
+        ("blocked", None, "none") => Ok(Query::Not(Box::new(Query::Has(QueryAttribute::Blocked)))),
+        ("blocked", None, "any") => Ok(Query::Has(QueryAttribute::Blocked)),
+        ("blocked", None, value) => match parse_as_id(value) {
+            Some(id) => Ok(Query::BlockedBy(id)),
+            None => Err(HyperTaskError::new(
+                HyperTaskErrorDomain::Input,
+                HyperTaskErrorAction::Parse,
+            )
+            .with_msg(|| format!("`{}` is not a valid id", value))),
+        },
+
+        ("parent", None, "none") => Ok(Query::Not(Box::new(Query::Has(QueryAttribute::Parent)))),
+        ("parent", None, "any") => Ok(Query::Has(QueryAttribute::Parent)),
+        ("parent", None, value) => match parse_as_id(value) {
+            Some(id) => Ok(Query::Parent(id)),
+            None => Err(HyperTaskError::new(
+                HyperTaskErrorDomain::Input,
+                HyperTaskErrorAction::Parse,
+            )
+            .with_msg(|| format!("`{}` is not a valid id", value))),
+        },
+        ("under", None, value) => match parse_as_id(value) {
+            Some(id) => Ok(Query::Subtree(id)),
+            None => Err(HyperTaskError::new(
+                HyperTaskErrorDomain::Input,
+                HyperTaskErrorAction::Parse,
+            )
+            .with_msg(|| format!("`{}` is not a valid id", value))),
+        },
+
+        ("template", None, value) => match parse_as_id(value) {
+            Some(id) => Ok(Query::Template(id)),
+            None => Err(HyperTaskError::new(
+                HyperTaskErrorDomain::Input,
+                HyperTaskErrorAction::Parse,
+            )
+            .with_msg(|| format!("`{}` is not a valid id", value))),
+        },
+
+        ("status", None, value) => value.parse::<Status>().map(Query::Status),
+
+        (name, comparison, value) if attribute_schema.get(name).is_some() => {
+            let attribute_type = attribute_schema.get(name).unwrap();
+            let name = name.to_string();
+
+            match comparison {
+                None => parse_as_attribute_value(&name, attribute_type, value)
+                    .map(|value| Query::AttributeEq(name, value)),
+                Some("below") | Some("before") => {
+                    parse_as_ordered_attribute_value(&name, attribute_type, value)
+                        .map(|value| Query::AttributeBelow(name, value))
+                }
+                Some("above") | Some("after") => {
+                    parse_as_ordered_attribute_value(&name, attribute_type, value)
+                        .map(|value| Query::AttributeAbove(name, value))
+                }
+                Some(_) => Err(HyperTaskError::new(
+                    HyperTaskErrorDomain::Input,
+                    HyperTaskErrorAction::Parse,
+                )
+                .with_msg(|| format!("`{}` is a malformed query attribute", token))),
+            }
+        }
+
+        (name, Some("before"), value) => parse_as_date_attribute_name(name).and_then(|attribute| {
+            parse_as_date_time(value).map(|date_time| Query::Before(attribute, date_time))
+        }),
+        (name, Some("after"), value) => parse_as_date_attribute_name(name).and_then(|attribute| {
+            parse_as_date_time(value).map(|date_time| Query::After(attribute, date_time))
+        }),
+
+        _ => Err(
+            HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                .with_msg(|| format!("`{}` is a malformed query attribute", token)),
+        ),
+    })
+}
+
+pub fn parse_as_query(
+    token: &str,
+    attribute_schema: &AttributeSchema,
+    working_set: &WorkingSet,
+) -> HyperTaskResult<Query> {
     if let Some(tag) = parse_as_tag(token) {
         return Ok(Query::Tag(tag));
     };
 
+    if token.len() > 1 && token.starts_with('/') && token.ends_with('/') {
+        return Ok(Query::Regex(DescriptionRegex::new(
+            &token[1..token.len() - 1],
+        )?));
+    };
+
+    if let Some(query) = parse_as_query_attribute(token, attribute_schema) {
+        return query;
+    };
+
+    // bare numbers refer to the tasks in the last listing
+    if let Some(number) = parse_as_working_set_number(token) {
+        return working_set.get_id(number).map(Query::Id);
+    };
+
     if let Some(id) = parse_as_id(token) {
         return Ok(Query::Id(id));
     };
@@ -135,6 +410,9 @@ fn end_of_year() -> DateTime<Utc> {
 
 lazy_static! {
     static ref DATE_SHORTCUT_REGEX: Regex = Regex::new(r"(\d+)([hdwmy])").unwrap();
+    static ref DURATION_REGEX: Regex = Regex::new(r"^(\d+)(min|h|d|w)$").unwrap();
+    static ref RECUR_REGEX: Regex = Regex::new(r"^(\d+)([dwmy])$").unwrap();
+    static ref MONTH_DAY_REGEX: Regex = Regex::new(r"^(1st|2nd|3rd|4th|5th|last)-(\w+)$").unwrap();
 }
 
 fn is_relative_date_shortcut(token: &str) -> bool {
@@ -186,41 +464,108 @@ pub fn parse_as_date_time(token: &str) -> HyperTaskResult<DateTime<Utc>> {
     }
 }
 
+fn recur_parse_error(token: &str) -> HyperTaskError {
+    HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+        .with_msg(|| format!("{} is not a valid recurence format", token))
+}
+
+fn parse_as_weekday(token: &str) -> Option<Weekday> {
+    match token {
+        "mon" => Some(Weekday::Mon),
+        "tue" => Some(Weekday::Tue),
+        "wed" => Some(Weekday::Wed),
+        "thu" => Some(Weekday::Thu),
+        "fri" => Some(Weekday::Fri),
+        "sat" => Some(Weekday::Sat),
+        "sun" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// Parses `recur:` values, eg: `3d`, `2w`, `mon,wed,fri`, `weekdays`, `2nd-tue`, `last-fri`,
+/// `last-day` or an iCalendar RRULE like `FREQ=WEEKLY;BYDAY=MO,FR`. Any of these can be
+/// prefixed with `after-` to schedule from when the task was done, eg: `after-3d`
 fn parse_as_recur(token: &str) -> HyperTaskResult<Recur> {
-    let caps = DATE_SHORTCUT_REGEX.captures(token).ok_or_else(|| {
-        HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
-            .with_msg(|| format!("{} is not a valid recurence format", token))
-    })?;
+    if let Some(recur_token) = token.strip_prefix("after-") {
+        return Ok(Recur {
+            anchor: RecurAnchor::Done,
+            ..parse_as_recur(recur_token)?
+        });
+    }
 
-    let number = caps
-        .get(1)
-        .ok_or_else(|| {
-            HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
-                .with_msg(|| format!("{} is not a valid recurence format", token))
-        })?
-        .as_str()
-        .parse::<i64>()
-        .unwrap();
-    let unit = caps
-        .get(2)
-        .ok_or_else(|| {
-            HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
-                .with_msg(|| format!("{} is not a valid recurence format", token))
-        })?
-        .as_str();
-
-    let recur = match (number, unit) {
-        (n, "d") => Recur::Day(n),
-        (n, "w") => Recur::Week(n),
-        (n, "m") => Recur::Month(n),
-        (n, "y") => Recur::Year(n),
-        (_, u) => panic!("{} is not a valid unit", u),
+    if token.starts_with("FREQ=") || token.starts_with("RRULE:") {
+        return Recur::from_rrule(token);
+    }
+
+    if token == "weekdays" {
+        return Ok(Recur {
+            weekdays: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+            ..Recur::every(Frequency::Week, 1)
+        });
+    }
+
+    if token == "last-day" {
+        return Ok(Recur {
+            month_day: Some(MonthDay::LastDay),
+            ..Recur::every(Frequency::Month, 1)
+        });
+    }
+
+    if let Some(caps) = MONTH_DAY_REGEX.captures(token) {
+        let n = match &caps[1] {
+            "last" => -1,
+            n => n[..1].parse::<i32>().unwrap(),
+        };
+        let weekday = parse_as_weekday(&caps[2]).ok_or_else(|| recur_parse_error(token))?;
+
+        return Ok(Recur {
+            month_day: Some(MonthDay::Weekday(n, weekday)),
+            ..Recur::every(Frequency::Month, 1)
+        });
+    }
+
+    if let Some(weekdays) = token
+        .split(',')
+        .map(parse_as_weekday)
+        .collect::<Option<Vec<Weekday>>>()
+    {
+        return Ok(Recur {
+            weekdays,
+            ..Recur::every(Frequency::Week, 1)
+        });
+    }
+
+    let caps = RECUR_REGEX
+        .captures(token)
+        .ok_or_else(|| recur_parse_error(token))?;
+
+    let number = match caps[1].parse::<i64>() {
+        Ok(n) if (1..=MAX_RECUR_INTERVAL).contains(&n) => n,
+        _ => return Err(recur_parse_error(token)),
     };
 
-    Ok(recur)
+    let frequency = match &caps[2] {
+        "d" => Frequency::Day,
+        "w" => Frequency::Week,
+        "m" => Frequency::Month,
+        "y" => Frequency::Year,
+        _ => return Err(recur_parse_error(token)),
+    };
+
+    Ok(Recur::every(frequency, number))
 }
 
-pub fn parse_as_prop(token: &str) -> Option<HyperTaskResult<Prop>> {
+pub fn parse_as_prop(
+    token: &str,
+    attribute_schema: &AttributeSchema,
+    working_set: &WorkingSet,
+) -> Option<HyperTaskResult<Prop>> {
     let colon_index = match token.chars().position(|c| c == ':') {
         Some(i) => i,
         None => return None,
@@ -263,19 +608,69 @@ pub fn parse_as_prop(token: &str) -> Option<HyperTaskResult<Prop>> {
             Ok(Prop::Recur(Some(value)))
         }
 
-        ("blocked", "") => Ok(Prop::Blocked(None)),
+        ("until", "") => Ok(Prop::RecurUntil(None)),
+        ("until", value) => {
+            let value = match parse_as_date_time(value) {
+                Ok(x) => x,
+                Err(msg) => return Some(Err(msg)),
+            };
+            Ok(Prop::RecurUntil(Some(value)))
+        }
+
+        ("count", "") => Ok(Prop::RecurCount(None)),
+        ("count", value) => match value.parse::<u32>() {
+            Ok(count) => Ok(Prop::RecurCount(Some(count))),
+            Err(e) => Err(HyperTaskError::new(
+                HyperTaskErrorDomain::Input,
+                HyperTaskErrorAction::Parse,
+            )
+            .with_msg(|| format!("`{}` is not a valid count", value))
+            .from(e)),
+        },
+
+        ("parent", "") => Ok(Prop::Parent(None)),
+        ("parent", value) => {
+            parse_as_task_reference(value, working_set).map(|id| Prop::Parent(Some(id)))
+        }
+
+        ("blocked", "") => Ok(Prop::Blocked(vec![])),
         ("blocked", value) => {
-            let value = match parse_as_id(&value) {
-                Some(x) => x,
-                None => {
-                    return Some(Err(HyperTaskError::new(
-                        HyperTaskErrorDomain::Input,
-                        HyperTaskErrorAction::Parse,
-                    )
-                    .with_msg(|| format!("`{}` is not a valid id", value))))
-                }
+            // `blocked:+<id>` and `blocked:-<id>` add or remove a single blocker,
+            // `blocked:<id>,<id>` replaces all of them
+            let (sign, ids) = match value.chars().next() {
+                Some('+') => (Some(Sign::Plus), &value[1..]),
+                Some('-') => (Some(Sign::Minus), &value[1..]),
+                _ => (None, value),
             };
-            Ok(Prop::Blocked(Some(value)))
+
+            let ids = match ids
+                .split(',')
+                .map(|id| parse_as_task_reference(id, working_set))
+                .collect::<HyperTaskResult<Vec<Id>>>()
+            {
+                Ok(ids) => ids,
+                Err(e) => return Some(Err(e)),
+            };
+
+            match (sign, ids.len()) {
+                (None, _) => Ok(Prop::Blocked(ids)),
+                (Some(sign), 1) => Ok(Prop::Blocker(sign, ids.into_iter().next().unwrap())),
+                (Some(_), _) => Err(HyperTaskError::new(
+                    HyperTaskErrorDomain::Input,
+                    HyperTaskErrorAction::Parse,
+                )
+                .with_msg(|| format!("`{}` can only add or remove one blocker", token))),
+            }
+        }
+
+        (name, value) if attribute_schema.get(name).is_some() => {
+            let attribute_type = attribute_schema.get(name).unwrap();
+
+            match value {
+                "" => Ok(Prop::Attribute(name.to_string(), None)),
+                value => parse_as_attribute_value(name, attribute_type, value)
+                    .map(|value| Prop::Attribute(name.to_string(), Some(value))),
+            }
         }
 
         _ => Err(
@@ -285,12 +680,16 @@ pub fn parse_as_prop(token: &str) -> Option<HyperTaskResult<Prop>> {
     })
 }
 
-pub fn parse_as_mutation(token: &str) -> HyperTaskResult<Mutation> {
+pub fn parse_as_mutation(
+    token: &str,
+    attribute_schema: &AttributeSchema,
+    working_set: &WorkingSet,
+) -> HyperTaskResult<Mutation> {
     if let Some(tag) = parse_as_tag(token) {
         return Ok(Mutation::SetTag(tag));
     };
 
-    match parse_as_prop(token) {
+    match parse_as_prop(token, attribute_schema, working_set) {
         Some(Ok(prop)) => return Ok(Mutation::SetProp(prop)),
         Some(Err(msg)) => return Err(msg),
         _ => {}
@@ -321,34 +720,81 @@ fn merge_description_mutations(mut mutations: Vec<Mutation>) -> Vec<Mutation> {
     output
 }
 
-pub fn parse_cli_args<'a>(args: impl Iterator<Item = &'a String>) -> HyperTaskResult<Command> {
+pub fn parse_cli_args<'a>(
+    args: impl Iterator<Item = &'a String>,
+    attribute_schema: &AttributeSchema,
+    working_set: &WorkingSet,
+) -> HyperTaskResult<Command> {
     let (query_tokens, command, mutation_tokens) = partition_args(args);
 
-    let parsed_queries: Vec<Query> = query_tokens
-        .iter()
-        .map(|q| parse_as_query(q))
-        .collect::<HyperTaskResult<Vec<Query>>>()
-        .map_err(|e| {
-            HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
-                .from(e)
-                .msg("could not parse queries")
-        })?;
+    // `add id:<id>` creates the task with a known id, or updates the task if it already exists
+    let (id_tokens, mutation_tokens): (Vec<&String>, Vec<&String>) = match command {
+        Some(CliCommand::Add) => mutation_tokens
+            .into_iter()
+            .partition(|token| token.starts_with(CREATE_ID_PREFIX)),
+        _ => (vec![], mutation_tokens),
+    };
+    let created_id: Option<Id> = id_tokens
+        .last()
+        .and_then(|token| token.strip_prefix(CREATE_ID_PREFIX))
+        .map(|id| Id(id.to_string()));
 
-    let parsed_mutations: Vec<Mutation> = mutation_tokens
-        .iter()
-        .map(|m| parse_as_mutation(m))
-        .collect::<HyperTaskResult<Vec<Mutation>>>()
-        .map_err(|e| {
+    let parsed_queries: Vec<Query> =
+        parse_as_query_expression(&query_tokens, attribute_schema, working_set).map_err(|e| {
             HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
                 .from(e)
                 .msg("could not parse queries")
         })?;
 
-    let parsed_mutations_with_merged_description = merge_description_mutations(parsed_mutations);
+    let parse_mutations = || -> HyperTaskResult<Vec<Mutation>> {
+        let parsed_mutations: Vec<Mutation> = mutation_tokens
+            .iter()
+            .map(|m| parse_as_mutation(m, attribute_schema, working_set))
+            .collect::<HyperTaskResult<Vec<Mutation>>>()
+            .map_err(|e| {
+                HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                    .from(e)
+                    .msg("could not parse queries")
+            })?;
+
+        let mut mutations = merge_description_mutations(parsed_mutations);
+
+        // `until:` and `count:` change the recurrence, so it has to be set first
+        mutations.sort_by_key(|m| match m {
+            Mutation::SetProp(Prop::Recur(_)) => 0,
+            _ => 1,
+        });
+
+        Ok(mutations)
+    };
+
+    // annotations are free text, so they aren't parsed as mutations
+    let annotation_text = || -> HyperTaskResult<String> {
+        if mutation_tokens.is_empty() {
+            Err(
+                HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                    .msg("annotations need some text"),
+            )
+        } else {
+            Ok(mutation_tokens
+                .iter()
+                .map(|token| token.as_str())
+                .collect::<Vec<&str>>()
+                .join(" "))
+        }
+    };
 
     match command {
-        Some(CliCommand::Add) => Ok(Command::Create(parsed_mutations_with_merged_description)),
+        Some(CliCommand::Add) => Ok(Command::Create(created_id, parse_mutations()?)),
+        Some(CliCommand::Annotate) => Ok(Command::Update(
+            parsed_queries,
+            vec![Mutation::Annotate(annotation_text()?)],
+        )),
         Some(CliCommand::Delete) => Ok(Command::Delete(parsed_queries)),
+        Some(CliCommand::Denotate) => Ok(Command::Update(
+            parsed_queries,
+            vec![Mutation::Denotate(annotation_text()?)],
+        )),
         Some(CliCommand::Done) => Ok(Command::Update(
             parsed_queries,
             vec![Mutation::SetProp(Prop::Done(Utc::now()))],
@@ -359,10 +805,26 @@ pub fn parse_cli_args<'a>(args: impl Iterator<Item = &'a String>) -> HyperTaskRe
                 Utc::now() + Duration::hours(1),
             )))],
         )),
-        Some(CliCommand::Modify) => Ok(Command::Update(
-            parsed_queries,
-            parsed_mutations_with_merged_description,
-        )),
+        Some(CliCommand::Info) | Some(CliCommand::Edit) => {
+            // the query can come before or after the command, `task 2 info` and `task info 2`
+            let info_queries = if parsed_queries.is_empty() {
+                parse_as_query_expression(&mutation_tokens, attribute_schema, working_set)?
+            } else {
+                parsed_queries
+            };
+
+            if info_queries.is_empty() {
+                Err(
+                    HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                        .msg("`info` and `edit` need a query for the tasks to show"),
+                )
+            } else {
+                Ok(Command::Read(info_queries))
+            }
+        }
+        Some(CliCommand::Start) => Ok(Command::Update(parsed_queries, vec![Mutation::Start])),
+        Some(CliCommand::Stop) => Ok(Command::Update(parsed_queries, vec![Mutation::Stop])),
+        Some(CliCommand::Modify) => Ok(Command::Update(parsed_queries, parse_mutations()?)),
         None => Ok(Command::Read(parsed_queries)),
     }
 }
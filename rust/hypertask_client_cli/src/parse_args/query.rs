@@ -0,0 +1,233 @@
+use super::parse_as_query;
+use crate::working_set::WorkingSet;
+use hypertask_engine::prelude::*;
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+#[derive(Debug, PartialEq)]
+enum QueryToken<'a> {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Term(&'a str),
+}
+
+/// Splits the query arguments into tokens, brackets can either be passed as their own
+/// argument, or attached to the start or end of a term: `(+work or +home)`
+fn tokenize<'a>(args: &[&'a String]) -> Vec<QueryToken<'a>> {
+    let mut tokens = vec![];
+
+    for arg in args {
+        let mut term: &str = arg.as_str();
+
+        while term.starts_with('(') {
+            tokens.push(QueryToken::Open);
+            term = &term[1..];
+        }
+
+        let mut closing_brackets = 0;
+        while term.ends_with(')') {
+            closing_brackets += 1;
+            term = &term[..term.len() - 1];
+        }
+
+        match term {
+            "" => {}
+            "and" => tokens.push(QueryToken::And),
+            "or" => tokens.push(QueryToken::Or),
+            "not" => tokens.push(QueryToken::Not),
+            term => tokens.push(QueryToken::Term(term)),
+        }
+
+        for _ in 0..closing_brackets {
+            tokens.push(QueryToken::Close);
+        }
+    }
+
+    tokens
+}
+
+fn query_parse_error(msg: &'static str) -> HyperTaskError {
+    HyperTaskError::new(HyperTaskErrorDomain::Query, HyperTaskErrorAction::Parse).msg(msg)
+}
+
+/// Joins a run of adjacent ids with `or`, and adds it to the queries being joined with `and`
+fn push_ids(queries: &mut Vec<Query>, ids: &mut Vec<Query>) {
+    match ids.len() {
+        0 => {}
+        1 => queries.push(ids.remove(0)),
+        _ => queries.push(Query::Or(std::mem::take(ids))),
+    }
+}
+
+struct QueryParser<'a> {
+    tokens: Peekable<IntoIter<QueryToken<'a>>>,
+    attribute_schema: &'a AttributeSchema,
+    working_set: &'a WorkingSet,
+}
+
+impl<'a> QueryParser<'a> {
+    fn parse_or(&mut self) -> HyperTaskResult<Query> {
+        let mut queries = vec![self.parse_and()?];
+
+        while self.tokens.peek() == Some(&QueryToken::Or) {
+            self.tokens.next();
+            queries.push(self.parse_and()?);
+        }
+
+        Ok(if queries.len() == 1 {
+            queries.remove(0)
+        } else {
+            Query::Or(queries)
+        })
+    }
+
+    fn parse_and(&mut self) -> HyperTaskResult<Query> {
+        let mut queries = vec![];
+        // bare ids next to each other are joined with `or` rather than `and`, so that
+        // `task <id> <id> done` acts on both of the tasks
+        let mut ids = vec![];
+
+        loop {
+            let is_term = matches!(self.tokens.peek(), Some(QueryToken::Term(_)));
+
+            match self.parse_unary()? {
+                query @ Query::Id(_) if is_term => ids.push(query),
+                query => {
+                    push_ids(&mut queries, &mut ids);
+                    queries.push(query);
+                }
+            }
+
+            match self.tokens.peek() {
+                Some(QueryToken::And) => {
+                    self.tokens.next();
+                    push_ids(&mut queries, &mut ids);
+                }
+                // terms next to each other are implicitly joined with `and`
+                Some(QueryToken::Open) | Some(QueryToken::Not) | Some(QueryToken::Term(_)) => {}
+                _ => break,
+            }
+        }
+
+        push_ids(&mut queries, &mut ids);
+
+        Ok(if queries.len() == 1 {
+            queries.remove(0)
+        } else {
+            Query::And(queries)
+        })
+    }
+
+    fn parse_unary(&mut self) -> HyperTaskResult<Query> {
+        match self.tokens.next() {
+            Some(QueryToken::Not) => Ok(Query::Not(Box::new(self.parse_unary()?))),
+            Some(QueryToken::Open) => {
+                let query = self.parse_or()?;
+
+                match self.tokens.next() {
+                    Some(QueryToken::Close) => Ok(query),
+                    _ => Err(query_parse_error("`(` is missing a matching `)`")),
+                }
+            }
+            Some(QueryToken::Term(term)) => {
+                parse_as_query(term, self.attribute_schema, self.working_set)
+            }
+            Some(QueryToken::Close) => Err(query_parse_error("unexpected `)`")),
+            Some(QueryToken::And) => Err(query_parse_error("unexpected `and`")),
+            Some(QueryToken::Or) => Err(query_parse_error("unexpected `or`")),
+            None => Err(query_parse_error("query ended unexpectedly")),
+        }
+    }
+}
+
+/// Parses the query arguments as a boolean expression made of query terms, `and`, `or`, `not`
+/// and brackets. Terms that are next to each other are joined with `and`, apart from ids, which
+/// are joined with `or`.
+pub fn parse_as_query_expression(
+    args: &[&String],
+    attribute_schema: &AttributeSchema,
+    working_set: &WorkingSet,
+) -> HyperTaskResult<Vec<Query>> {
+    let tokens = tokenize(args);
+
+    if tokens.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut parser = QueryParser {
+        tokens: tokens.into_iter().peekable(),
+        attribute_schema,
+        working_set,
+    };
+
+    let query = parser.parse_or()?;
+
+    if parser.tokens.next().is_some() {
+        return Err(query_parse_error("unexpected `)`"));
+    }
+
+    Ok(match query {
+        Query::And(queries) => queries,
+        query => vec![query],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> HyperTaskResult<String> {
+        let args: Vec<String> = input.split_whitespace().map(|s| s.to_owned()).collect();
+        let arg_refs: Vec<&String> = args.iter().collect();
+
+        parse_as_query_expression(
+            &arg_refs,
+            &AttributeSchema::default(),
+            &WorkingSet::default(),
+        )
+        .map(|queries| format!("{:?}", queries))
+    }
+
+    #[test]
+    fn joins_adjacent_terms_with_and() {
+        assert_eq!(
+            parse("+work +urgent").unwrap(),
+            parse("+work and +urgent").unwrap()
+        );
+    }
+
+    #[test]
+    fn joins_adjacent_ids_with_or() {
+        assert_eq!(parse("abcd efgh").unwrap(), parse("abcd or efgh").unwrap());
+        assert_eq!(
+            parse("abcd efgh +work").unwrap(),
+            parse("(abcd or efgh) +work").unwrap()
+        );
+        assert_ne!(
+            parse("abcd and efgh").unwrap(),
+            parse("abcd or efgh").unwrap()
+        );
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            parse("+a +b or +c").unwrap(),
+            parse("((+a +b) or +c)").unwrap()
+        );
+        assert_ne!(
+            parse("+a +b or +c").unwrap(),
+            parse("+a (+b or +c)").unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_unbalanced_brackets() {
+        assert!(parse("(+a or +b").is_err());
+        assert!(parse("+a or +b)").is_err());
+        assert!(parse("not").is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::config::CliConfig;
-use ansi_term::Colour::{Cyan, Green, Red};
+use crate::working_set::parse_as_working_set_number;
+use ansi_term::Colour::{Cyan, Green, Red, Yellow};
 use ansi_term::Style;
 use chrono::prelude::*;
 use hypertask_engine::prelude::*;
@@ -8,28 +9,47 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
 use std::rc::Rc;
+use time::Duration;
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash)]
 pub enum RenderColumns {
+    /// the task's number in the listing, which can be used in place of its id
+    Number,
     Id,
     Score,
     Description,
     Blocked,
+    Parent,
     Tags,
     Due,
     Recur,
+    Annotations,
+    /// whether the task is being worked on
+    Active,
+    /// the total time spent working on the task
+    TimeSpent,
+    Status,
+    /// a user defined attribute
+    Attribute(String),
 }
 
 impl fmt::Display for RenderColumns {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.pad(match self {
+            RenderColumns::Number => "#",
             RenderColumns::Id => "Id",
             RenderColumns::Score => "Score",
             RenderColumns::Description => "Description",
             RenderColumns::Blocked => "Blocked By",
+            RenderColumns::Parent => "Parent",
             RenderColumns::Tags => "Tags",
             RenderColumns::Due => "Due",
             RenderColumns::Recur => "Recur",
+            RenderColumns::Annotations => "Notes",
+            RenderColumns::Active => "Active",
+            RenderColumns::TimeSpent => "Spent",
+            RenderColumns::Status => "Status",
+            RenderColumns::Attribute(name) => name,
         })
     }
 }
@@ -56,22 +76,76 @@ fn style_task(filtered: bool, score: f64) -> Style {
     style
 }
 
-fn format_date_time(dt: DateTime<Utc>) -> String {
+pub fn format_date_time(dt: DateTime<Utc>) -> String {
     dt.format("%Y-%m-%d %H:%M").to_string()
 }
 
+/// Formats tracked time as hours and minutes, eg: `2h 05min`
+pub fn format_time_spent(time_spent: Duration) -> String {
+    match (time_spent.num_hours(), time_spent.num_minutes() % 60) {
+        (0, minutes) => format!("{}min", minutes),
+        (hours, minutes) => format!("{}h {:02}min", hours, minutes),
+    }
+}
+
+/// The shortest unique prefix of an id, made longer if it's only digits, as bare numbers refer
+/// to the tasks in the last listing
+fn format_short_id(id: &Id, short_id: &str) -> String {
+    let mut length = short_id.chars().count();
+
+    while parse_as_working_set_number(id.prefix(length)).is_some()
+        && length < NUMBER_OF_CHARS_IN_FULL_ID
+    {
+        length += 1;
+    }
+
+    id.prefix(length).to_string()
+}
+
 fn renderify_task(
     input: &(bool, f64, Rc<Task>),
+    number: Option<usize>,
+    engine_output: &EngineOutput,
+    now: &DateTime<Utc>,
 ) -> (ansi_term::Style, HashMap<RenderColumns, String>) {
     let (filtered, score, task) = input;
 
     let mut map = HashMap::new();
-    map.insert(RenderColumns::Id, format!("{}", task.get_id()));
+    map.insert(
+        RenderColumns::Number,
+        number.map(|number| number.to_string()).unwrap_or_default(),
+    );
+    map.insert(
+        RenderColumns::Status,
+        engine_output
+            .statuses
+            .get(&task.get_id())
+            .map(|status| status.to_string())
+            .unwrap_or_default(),
+    );
+    map.insert(
+        RenderColumns::Id,
+        match engine_output.short_ids.get(&task.get_id()) {
+            Some(short_id) => format_short_id(&task.get_id(), short_id),
+            None => format!("{}", task.get_id()),
+        },
+    );
     map.insert(RenderColumns::Score, format!("{0:.4}", score));
+    map.insert(RenderColumns::Blocked, {
+        let mut vec = task
+            .get_blocked_by()
+            .iter()
+            .map(|id| format!("{}", id))
+            .collect::<Vec<String>>();
+        vec.sort();
+        vec.join(" ")
+    });
+
     map.insert(
-        RenderColumns::Blocked,
-        task.get_blocked_by()
-            .map(|d| format!("{}", d))
+        RenderColumns::Parent,
+        task.get_parent()
+            .as_ref()
+            .map(|id| format!("{}", id))
             .unwrap_or_else(String::default),
     );
 
@@ -100,6 +174,39 @@ fn renderify_task(
         vec.join(" ")
     });
 
+    map.insert(
+        RenderColumns::Annotations,
+        match task.get_annotations().len() {
+            0 => String::default(),
+            count => format!("{}", count),
+        },
+    );
+
+    map.insert(
+        RenderColumns::Active,
+        if task.is_active() {
+            "*".to_string()
+        } else {
+            String::default()
+        },
+    );
+
+    map.insert(
+        RenderColumns::TimeSpent,
+        if task.get_intervals().is_empty() {
+            String::default()
+        } else {
+            format_time_spent(task.get_time_spent(now))
+        },
+    );
+
+    for (name, value) in task.get_attributes() {
+        map.insert(
+            RenderColumns::Attribute(name.to_string()),
+            format!("{}", value),
+        );
+    }
+
     map.insert(
         RenderColumns::Description,
         task.get_description()
@@ -111,12 +218,24 @@ fn renderify_task(
     (style_task(*filtered, *score), map)
 }
 
+/// Renders the display tasks as a table, tasks are only `numbered` when they're a listing that
+/// has been saved as the working set
 pub fn render_engine_output(
-    display_tasks: Vec<(bool, Score, Rc<Task>)>,
+    engine_output: &EngineOutput,
     cli_config: &CliConfig,
+    now: &DateTime<Utc>,
+    numbered: bool,
 ) -> HyperTaskResult<()> {
-    let renderable_tasks: Vec<(ansi_term::Style, HashMap<RenderColumns, String>)> =
-        display_tasks.iter().map(renderify_task).collect();
+    let renderable_tasks: Vec<(ansi_term::Style, HashMap<RenderColumns, String>)> = engine_output
+        .display_tasks
+        .iter()
+        .enumerate()
+        .map(|(index, task)| {
+            let number = if numbered { Some(index + 1) } else { None };
+
+            renderify_task(task, number, engine_output, now)
+        })
+        .collect();
 
     render_table(
         &cli_config.render.columns,
@@ -127,3 +246,31 @@ pub fn render_engine_output(
         HyperTaskError::new(HyperTaskErrorDomain::Render, HyperTaskErrorAction::Write).from(e)
     })
 }
+
+/// Prints the changes that a dry run would have made, field by field
+pub fn render_task_diffs(diffs: &[TaskDiff]) {
+    if diffs.is_empty() {
+        println!("no tasks would change");
+    }
+
+    for diff in diffs {
+        if diff.is_new {
+            println!(
+                "{} {}",
+                Style::new().bold().paint(&diff.id),
+                Yellow.paint("(new)")
+            );
+        } else {
+            println!("{}", Style::new().bold().paint(&diff.id));
+        }
+
+        for change in &diff.changes {
+            println!(
+                "  {}: {} -> {}",
+                change.field,
+                Red.paint(change.before.as_deref().unwrap_or("none")),
+                Green.paint(change.after.as_deref().unwrap_or("none"))
+            );
+        }
+    }
+}
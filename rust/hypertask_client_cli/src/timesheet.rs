@@ -0,0 +1,71 @@
+use crate::config::CliConfig;
+use crate::parse_args::parse_as_date_time;
+use crate::render::format_time_spent;
+use chrono::prelude::*;
+use hypertask_engine::prelude::*;
+use hypertask_task_io_operations::get_input_tasks;
+use std::collections::BTreeMap;
+use time::Duration;
+
+const UNTAGGED: &str = "(untagged)";
+
+/// Totals the time tracked on tasks by tag, `task timesheet <from> <to>` reports between two
+/// dates, and the last week is reported by default. Tasks with several tags count towards each
+pub fn run_timesheet(
+    cli_config: &CliConfig,
+    args: &[String],
+    now: &DateTime<Utc>,
+) -> HyperTaskResult<()> {
+    let from = match args.first() {
+        Some(arg) => parse_as_date_time(arg)?,
+        None => *now - Duration::weeks(1),
+    };
+    let to = match args.get(1) {
+        Some(arg) => parse_as_date_time(arg)?,
+        None => *now,
+    };
+
+    let mut totals: BTreeMap<String, Duration> = BTreeMap::new();
+    let mut total = Duration::zero();
+
+    for task in get_input_tasks(cli_config)?.values() {
+        let time_spent = task.get_time_spent_between(&from, &to, now);
+
+        if time_spent <= Duration::zero() {
+            continue;
+        }
+
+        total = total + time_spent;
+
+        let mut tags: Vec<&str> = task.get_tags().iter().map(|tag| tag.as_str()).collect();
+
+        if tags.is_empty() {
+            tags.push(UNTAGGED);
+        }
+
+        for tag in tags {
+            let tag_total = totals.entry(tag.to_string()).or_insert_with(Duration::zero);
+            *tag_total = *tag_total + time_spent;
+        }
+    }
+
+    println!(
+        "{} to {}",
+        from.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+        to.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+    );
+
+    for (tag, time_spent) in totals {
+        let label = if tag == UNTAGGED {
+            tag
+        } else {
+            format!("+{}", tag)
+        };
+
+        println!("{:>12} {}", format_time_spent(time_spent), label);
+    }
+
+    println!("{:>12} total", format_time_spent(total));
+
+    Ok(())
+}
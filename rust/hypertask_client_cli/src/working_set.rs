@@ -0,0 +1,108 @@
+use hypertask_engine::prelude::*;
+use hypertask_task_io_operations::WorkingSetEntry;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The numbers given to the tasks in the last listing, `task 2 done` refers to the second task
+/// that was shown. A number is `None` once its task has changed, or been deleted, since it was
+/// listed, so that it can't be used to act on the wrong task
+#[derive(Debug, Default)]
+pub struct WorkingSet {
+    ids: Vec<Option<Id>>,
+}
+
+impl WorkingSet {
+    pub fn new(entries: Vec<WorkingSetEntry>, tasks: &HashMap<Rc<Id>, Rc<Task>>) -> Self {
+        Self {
+            ids: entries
+                .into_iter()
+                .map(|entry| match tasks.get(&entry.id) {
+                    Some(task) if *task.get_updated_at() == entry.updated_at => Some(entry.id),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn get_id(&self, number: usize) -> HyperTaskResult<Id> {
+        match number.checked_sub(1).and_then(|index| self.ids.get(index)) {
+            Some(Some(id)) => Ok(id.clone()),
+            Some(None) => Err(HyperTaskError::new(
+                HyperTaskErrorDomain::Input,
+                HyperTaskErrorAction::Parse,
+            )
+            .with_msg(|| {
+                format!(
+                    "task `{}` has changed since it was listed, list the tasks again to renumber them",
+                    number
+                )
+            })),
+            None => Err(
+                HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                    .with_msg(|| format!("there is no task `{}` in the last listing", number)),
+            ),
+        }
+    }
+}
+
+/// Whether a token refers to a task by its number in the last listing
+pub fn parse_as_working_set_number(token: &str) -> Option<usize> {
+    if !token.is_empty() && token.chars().all(|c| c.is_ascii_digit()) {
+        token.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Numbers the listed tasks in the order they're shown, which is by score
+pub fn get_working_set_entries(engine_output: &EngineOutput) -> Vec<WorkingSetEntry> {
+    engine_output
+        .display_tasks
+        .iter()
+        .map(|(_, _, task)| WorkingSetEntry {
+            id: (*task.get_id()).clone(),
+            updated_at: *task.get_updated_at(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::*;
+
+    #[test]
+    fn refuses_numbers_of_tasks_that_have_changed() {
+        let listed_at = Utc.ymd(2015, 11, 15).and_hms(9, 10, 11);
+        let unchanged = Task::generate(&listed_at);
+        let changed = Task::generate(&listed_at);
+
+        let entries = vec![
+            WorkingSetEntry {
+                id: (*unchanged.get_id()).clone(),
+                updated_at: listed_at,
+            },
+            WorkingSetEntry {
+                id: (*changed.get_id()).clone(),
+                updated_at: listed_at,
+            },
+        ];
+
+        let mut tasks = HashMap::new();
+        tasks.insert(unchanged.get_id(), Rc::new(unchanged.clone()));
+        tasks.insert(
+            changed.get_id(),
+            Rc::new(changed.apply_mutations(
+                &[Mutation::Start],
+                &Utc.ymd(2015, 11, 16).and_hms(9, 10, 11),
+            )),
+        );
+
+        let working_set = WorkingSet::new(entries, &tasks);
+
+        assert_eq!(working_set.get_id(1).unwrap(), *unchanged.get_id());
+        assert!(working_set.get_id(2).is_err());
+        assert!(working_set.get_id(3).is_err());
+        assert!(working_set.get_id(0).is_err());
+    }
+}
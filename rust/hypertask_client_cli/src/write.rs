@@ -0,0 +1,99 @@
+use crate::config::CliConfig;
+use ansi_term::Colour::Yellow;
+use chrono::prelude::*;
+use hypertask_config_file_opener::run_string_as_shell_command;
+use hypertask_engine::prelude::*;
+use hypertask_task_io_operations::{append_journal_entry, put_task, JournalChange, JournalEntry};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Writes each task that a command changed, running the `on_edit` hook after each one
+pub fn put_changed_tasks<'a>(
+    cli_config: &CliConfig,
+    tasks: impl Iterator<Item = &'a Task>,
+) -> HyperTaskResult<()> {
+    for task in tasks {
+        put_task(cli_config, task)?;
+        if let Some(on_edit_cmd) = cli_config
+            .hooks
+            .as_ref()
+            .and_then(|config| config.on_edit.as_ref())
+        {
+            run_string_as_shell_command(on_edit_cmd)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the `after` hook once everything that a command changed has been written, and prints
+/// what it output
+pub fn run_after_hook(cli_config: &CliConfig) -> HyperTaskResult<()> {
+    if let Some(after_cmd) = cli_config
+        .hooks
+        .as_ref()
+        .and_then(|config| config.after.as_ref())
+    {
+        print!("{}", run_string_as_shell_command(after_cmd)?);
+    }
+
+    Ok(())
+}
+
+/// Writes the tasks that the engine changed, and records them in the journal so that they can be
+/// undone
+pub fn write_engine_output(
+    cli_config: &CliConfig,
+    engine_output: &EngineOutput,
+    original_tasks: &HashMap<Rc<Id>, Rc<Task>>,
+    command: &str,
+    now: &DateTime<Utc>,
+) -> HyperTaskResult<()> {
+    let EngineOutput {
+        mutated_tasks,
+        deleted_tasks,
+        ..
+    } = engine_output;
+
+    if mutated_tasks.is_empty() && deleted_tasks.is_empty() {
+        return Ok(());
+    }
+
+    put_changed_tasks(
+        cli_config,
+        mutated_tasks
+            .iter()
+            .chain(deleted_tasks.iter())
+            .map(|task| &**task),
+    )?;
+
+    // the tasks have already been written, so a journal that can't be updated only means the
+    // command can't be undone
+    if let Err(e) = append_journal_entry(
+        cli_config,
+        &JournalEntry {
+            created_at: *now,
+            command: command.to_string(),
+            changes: mutated_tasks
+                .iter()
+                .chain(deleted_tasks.iter())
+                .map(|task| JournalChange {
+                    before: original_tasks
+                        .get(&task.get_id())
+                        .map(|original| (**original).clone()),
+                    after: (**task).clone(),
+                })
+                .collect(),
+        },
+    ) {
+        eprintln!(
+            "{}",
+            Yellow.paint(format!(
+                "warning: the changes can't be undone, the journal could not be updated: {}",
+                e
+            ))
+        );
+    }
+
+    run_after_hook(cli_config)
+}
@@ -1,7 +1,6 @@
 use chrono::prelude::*;
 use hypertask_engine::prelude::*;
 use rand::prelude::*;
-use rand::seq::IteratorRandom;
 use std::collections::HashMap;
 use wasm_bindgen::prelude::*;
 
@@ -95,18 +94,9 @@ impl<'a> HyperTaskEngineContext<WebTaskIterator> for WebContext<'a> {
     }
 
     fn generate_id(&mut self) -> String {
-        let mut result = String::new();
+        let Id(id) = Id::generate_with(&mut self.rng);
 
-        for _ in 0..NUMBER_OF_CHARS_IN_FULL_ID {
-            let random = VALID_ID_CHARS
-                .chars()
-                .choose(&mut self.rng)
-                .expect("Couldn't get random char");
-
-            result.push(random);
-        }
-
-        result
+        id
     }
 
     fn get_task_iterator(&self) -> HyperTaskResult<WebTaskIterator> {
@@ -0,0 +1,36 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A timestamped note about a task
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Annotation {
+    pub created_at: DateTime<Utc>,
+    pub text: String,
+    /// when the note was removed, removed notes are kept so that the removal can be synced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub removed_at: Option<DateTime<Utc>>,
+}
+
+impl Annotation {
+    pub fn is_removed(&self) -> bool {
+        self.removed_at.is_some()
+    }
+
+    /// Whether both are the same note, even if only one of them has been removed
+    pub fn is_same_note(&self, other: &Annotation) -> bool {
+        self.created_at == other.created_at && self.text == other.text
+    }
+}
+
+impl fmt::Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            self.created_at.format("%Y-%m-%d %H:%M"),
+            self.text
+        )
+    }
+}
@@ -0,0 +1,120 @@
+use crate::error::*;
+use chrono::prelude::*;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// The type of a user defined attribute, as declared in the config
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeType {
+    String,
+    Number,
+    Date,
+    Duration,
+    /// one of a fixed list of values, ordered from lowest to highest
+    Enum(Vec<String>),
+}
+
+impl AttributeType {
+    /// Values of ordered types can be compared with each other in queries
+    pub fn is_ordered(&self) -> bool {
+        match self {
+            AttributeType::Number | AttributeType::Date | AttributeType::Duration => true,
+            AttributeType::String | AttributeType::Enum(_) => false,
+        }
+    }
+}
+
+/// The value of a user defined attribute, durations are stored in seconds
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeValue {
+    String(String),
+    Number(f64),
+    Date(DateTime<Utc>),
+    Duration(i64),
+    Enum(String),
+}
+
+impl Eq for AttributeValue {}
+
+impl Hash for AttributeValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            AttributeValue::String(s) => s.hash(state),
+            AttributeValue::Number(n) => n.to_bits().hash(state),
+            AttributeValue::Date(d) => d.hash(state),
+            AttributeValue::Duration(d) => d.hash(state),
+            AttributeValue::Enum(s) => s.hash(state),
+        }
+    }
+}
+
+impl AttributeValue {
+    /// Compares values of the same ordered type, anything else can't be compared
+    pub fn compare(&self, other: &AttributeValue) -> Option<Ordering> {
+        match (self, other) {
+            (AttributeValue::Number(a), AttributeValue::Number(b)) => a.partial_cmp(b),
+            (AttributeValue::Date(a), AttributeValue::Date(b)) => Some(a.cmp(b)),
+            (AttributeValue::Duration(a), AttributeValue::Duration(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+}
+
+fn format_duration(seconds: i64) -> String {
+    match seconds {
+        s if s != 0 && s % (60 * 60 * 24 * 7) == 0 => format!("{}w", s / (60 * 60 * 24 * 7)),
+        s if s != 0 && s % (60 * 60 * 24) == 0 => format!("{}d", s / (60 * 60 * 24)),
+        s if s != 0 && s % (60 * 60) == 0 => format!("{}h", s / (60 * 60)),
+        s => format!("{}min", s / 60),
+    }
+}
+
+impl fmt::Display for AttributeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttributeValue::String(s) | AttributeValue::Enum(s) => write!(f, "{}", s),
+            AttributeValue::Number(n) => write!(f, "{}", n),
+            AttributeValue::Date(d) => write!(f, "{}", d.format("%Y-%m-%d %H:%M")),
+            AttributeValue::Duration(seconds) => write!(f, "{}", format_duration(*seconds)),
+        }
+    }
+}
+
+/// The user defined attributes that tasks can have, by name
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct AttributeSchema(HashMap<String, AttributeType>);
+
+impl AttributeSchema {
+    pub fn new(attributes: HashMap<String, AttributeType>) -> Self {
+        AttributeSchema(attributes)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AttributeType> {
+        self.0.get(name)
+    }
+
+    /// Converts a value to a number for score calculations, dates become timestamps, durations
+    /// become seconds and enums become their position in the list of values, starting at 1
+    pub fn to_number(&self, name: &str, value: &AttributeValue) -> HyperTaskResult<f64> {
+        match (value, self.get(name)) {
+            (AttributeValue::Number(n), _) => Ok(*n),
+            (AttributeValue::Date(d), _) => Ok(d.timestamp() as f64),
+            (AttributeValue::Duration(seconds), _) => Ok(*seconds as f64),
+            (AttributeValue::Enum(s), Some(AttributeType::Enum(values))) => Ok(values
+                .iter()
+                .position(|v| v == s)
+                .map(|i| i + 1)
+                .unwrap_or(0)
+                as f64),
+            _ => Err(HyperTaskError::new(
+                HyperTaskErrorDomain::ScoreCalculator,
+                HyperTaskErrorAction::Run,
+            )
+            .with_msg(|| format!("`{}` can't be used as a number", name))),
+        }
+    }
+}
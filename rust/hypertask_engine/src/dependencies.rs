@@ -0,0 +1,294 @@
+use crate::id::Id;
+use crate::task::Task;
+use chrono::prelude::*;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Metrics about everything that is waiting on a task, following open dependants all the way down
+/// the chain
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TransitiveMetrics {
+    /// the number of open tasks that are directly or indirectly blocked by the task
+    pub dependants: usize,
+    /// the earliest due date of any of those tasks
+    pub earliest_due: Option<DateTime<Utc>>,
+    /// the length of the longest chain of open tasks that are blocked by the task
+    pub depth: usize,
+}
+
+/// The blockers of every task, the tasks that each task is blocking, and the children of every
+/// parent task
+pub struct DependencyGraph {
+    all_tasks_collection: HashMap<Rc<Id>, Rc<Task>>,
+    dependants_map: HashMap<Rc<Id>, Vec<Rc<Task>>>,
+    children_map: HashMap<Rc<Id>, Vec<Rc<Task>>>,
+    /// worked out the first time they're asked for, as most score programs don't use them
+    transitive_metrics_map: RefCell<HashMap<Rc<Id>, TransitiveMetrics>>,
+    depths: RefCell<HashMap<Rc<Id>, usize>>,
+}
+
+impl DependencyGraph {
+    pub fn new(all_tasks_collection: &HashMap<Rc<Id>, Rc<Task>>) -> Self {
+        let mut dependants_map: HashMap<Rc<Id>, Vec<Rc<Task>>> = HashMap::new();
+        let mut children_map: HashMap<Rc<Id>, Vec<Rc<Task>>> = HashMap::new();
+
+        for child_task in all_tasks_collection.values() {
+            for parent_id in child_task.get_blocked_by() {
+                dependants_map
+                    .entry(parent_id.clone())
+                    .or_default()
+                    .push(child_task.clone());
+            }
+
+            if let Some(parent_id) = child_task.get_parent() {
+                children_map
+                    .entry(parent_id.clone())
+                    .or_default()
+                    .push(child_task.clone());
+            }
+        }
+
+        Self {
+            all_tasks_collection: all_tasks_collection.clone(),
+            dependants_map,
+            children_map,
+            transitive_metrics_map: RefCell::new(HashMap::new()),
+            depths: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn get_open_dependants<'a>(&'a self, id: &Id) -> impl Iterator<Item = &'a Rc<Task>> {
+        self.get_dependants(id)
+            .iter()
+            .filter(|task| task.get_done().is_none())
+    }
+
+    fn collect_transitive_dependants(&self, id: &Rc<Id>) -> TransitiveMetrics {
+        let mut metrics = TransitiveMetrics::default();
+        let mut visited: HashSet<Rc<Id>> = HashSet::new();
+        let mut stack: Vec<Rc<Id>> = vec![id.clone()];
+
+        visited.insert(id.clone());
+
+        while let Some(current_id) = stack.pop() {
+            for dependant in self.get_open_dependants(&current_id) {
+                if visited.insert(dependant.get_id()) {
+                    metrics.dependants += 1;
+
+                    if let Some(due) = dependant.get_due() {
+                        metrics.earliest_due = Some(
+                            metrics
+                                .earliest_due
+                                .map_or(*due, |earliest| earliest.min(*due)),
+                        );
+                    }
+
+                    stack.push(dependant.get_id());
+                }
+            }
+        }
+
+        metrics
+    }
+
+    /// Memoised longest path through the open dependants, worked out without recursion so that
+    /// long chains can't overflow the stack. `ancestors` stops us from looping forever if the
+    /// stored tasks already contain a cycle
+    fn get_depth(&self, id: &Rc<Id>) -> usize {
+        let mut depths = self.depths.borrow_mut();
+        let mut ancestors: HashSet<Rc<Id>> = HashSet::new();
+        // each task is visited twice, once to add its dependants, and again once they're done
+        let mut stack: Vec<(Rc<Id>, bool)> = vec![(id.clone(), false)];
+
+        while let Some((current_id, has_visited_dependants)) = stack.pop() {
+            if has_visited_dependants {
+                let depth = self
+                    .get_open_dependants(&current_id)
+                    .map(|dependant| 1 + depths.get(&dependant.get_id()).copied().unwrap_or(0))
+                    .max()
+                    .unwrap_or(0);
+
+                ancestors.remove(&current_id);
+                depths.insert(current_id, depth);
+            } else if !depths.contains_key(&current_id) && ancestors.insert(current_id.clone()) {
+                stack.push((current_id.clone(), true));
+                stack.extend(
+                    self.get_open_dependants(&current_id)
+                        .map(|dependant| (dependant.get_id(), false)),
+                );
+            }
+        }
+
+        depths.get(id).copied().unwrap_or(0)
+    }
+
+    /// Gets the metrics about everything downstream of the task with `id`
+    pub fn get_transitive_metrics(&self, id: &Id) -> Option<TransitiveMetrics> {
+        let (id, _) = self.all_tasks_collection.get_key_value(id)?;
+
+        if let Some(metrics) = self.transitive_metrics_map.borrow().get(id) {
+            return Some(metrics.clone());
+        }
+
+        let metrics = TransitiveMetrics {
+            depth: self.get_depth(id),
+            ..self.collect_transitive_dependants(id)
+        };
+
+        self.transitive_metrics_map
+            .borrow_mut()
+            .insert(id.clone(), metrics.clone());
+
+        Some(metrics)
+    }
+
+    /// Gets the tasks that are directly blocked by the task with `id`
+    pub fn get_dependants(&self, id: &Id) -> &[Rc<Task>] {
+        self.dependants_map
+            .get(id)
+            .map(|dependants| dependants.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Gets the tasks whose parent is the task with `id`
+    pub fn get_children(&self, id: &Id) -> &[Rc<Task>] {
+        self.children_map
+            .get(id)
+            .map(|children| children.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn get_open_children<'a>(&'a self, id: &Id) -> impl Iterator<Item = &'a Rc<Task>> {
+        self.get_children(id)
+            .iter()
+            .filter(|task| task.get_done().is_none())
+    }
+
+    /// Gets the ids of every task beneath the task with `id`, not including the task itself
+    pub fn get_descendants(&self, id: &Id) -> HashSet<Rc<Id>> {
+        let mut descendants: HashSet<Rc<Id>> = HashSet::new();
+        let mut stack: Vec<&Rc<Task>> = self.get_children(id).iter().collect();
+
+        while let Some(task) = stack.pop() {
+            if descendants.insert(task.get_id()) {
+                stack.extend(self.get_children(&task.get_id()));
+            }
+        }
+
+        descendants
+    }
+
+    /// The percentage of the task's descendants that have been done, `None` if it has no children
+    pub fn get_progress(&self, id: &Id) -> Option<f64> {
+        let descendants = self.get_descendants(id);
+
+        if descendants.is_empty() {
+            return None;
+        }
+
+        let done = descendants
+            .iter()
+            .filter_map(|id| self.all_tasks_collection.get(id))
+            .filter(|task| task.get_done().is_some())
+            .count();
+
+        Some(100.0 * done as f64 / descendants.len() as f64)
+    }
+
+    /// Gets the parent of `task` if it doesn't refer to any task that we know about
+    pub fn get_dangling_parent<'a>(&'a self, task: &'a Task) -> Option<&'a Rc<Id>> {
+        task.get_parent()
+            .as_ref()
+            .filter(|id| !self.all_tasks_collection.contains_key(*id))
+    }
+
+    /// Follows the parents of the task with `start_id`, if they lead back to the task then the
+    /// path taken is returned, starting and ending with `start_id`
+    pub fn find_parent_cycle(&self, start_id: &Rc<Id>) -> Option<Vec<Rc<Id>>> {
+        let mut path = vec![start_id.clone()];
+        let mut current_id = start_id.clone();
+
+        while let Some(parent_id) = self
+            .all_tasks_collection
+            .get(&current_id)
+            .and_then(|task| task.get_parent().clone())
+        {
+            path.push(parent_id.clone());
+
+            if &parent_id == start_id {
+                return Some(path);
+            }
+
+            // a cycle further up that doesn't include this task isn't ours to report
+            if path[..path.len() - 1].contains(&parent_id) {
+                return None;
+            }
+
+            current_id = parent_id;
+        }
+
+        None
+    }
+
+    /// A blocker is open if its task exists and has not been done
+    pub fn is_open_blocker(&self, id: &Id) -> bool {
+        self.all_tasks_collection
+            .get(id)
+            .map(|task| task.get_done().is_none())
+            .unwrap_or(false)
+    }
+
+    pub fn get_open_blockers<'a>(&'a self, task: &'a Task) -> impl Iterator<Item = &'a Rc<Id>> {
+        task.get_blocked_by()
+            .iter()
+            .filter(move |id| self.is_open_blocker(id))
+    }
+
+    /// Gets the blockers of `task` that don't refer to any task that we know about
+    pub fn get_dangling_blockers<'a>(&'a self, task: &'a Task) -> impl Iterator<Item = &'a Rc<Id>> {
+        task.get_blocked_by()
+            .iter()
+            .filter(move |id| !self.all_tasks_collection.contains_key(*id))
+    }
+
+    /// Follows the blockers of the task with `start_id`, if they lead back to the task then the
+    /// path taken is returned, starting and ending with `start_id`
+    pub fn find_cycle(&self, start_id: &Rc<Id>) -> Option<Vec<Rc<Id>>> {
+        let get_blockers = |id: &Id| -> Vec<Rc<Id>> {
+            self.all_tasks_collection
+                .get(id)
+                .map(|task| task.get_blocked_by().iter().cloned().collect())
+                .unwrap_or_default()
+        };
+
+        let mut visited = HashSet::new();
+        // the path taken so far, each with the blockers that are still to be followed from it,
+        // kept on the heap so that long chains can't overflow the stack
+        let mut path: Vec<(Rc<Id>, Vec<Rc<Id>>)> = vec![(start_id.clone(), get_blockers(start_id))];
+
+        while let Some((_, blockers)) = path.last_mut() {
+            let blocker_id = match blockers.pop() {
+                Some(blocker_id) => blocker_id,
+                None => {
+                    path.pop();
+                    continue;
+                }
+            };
+
+            if &blocker_id == start_id {
+                let mut cycle: Vec<Rc<Id>> = path.into_iter().map(|(id, _)| id).collect();
+                cycle.push(blocker_id);
+
+                return Some(cycle);
+            }
+
+            if visited.insert(blocker_id.clone()) {
+                let blockers = get_blockers(&blocker_id);
+                path.push((blocker_id, blockers));
+            }
+        }
+
+        None
+    }
+}
@@ -1,114 +1,568 @@
+use crate::dependencies::DependencyGraph;
 use crate::error::*;
-use crate::id::Id;
+use crate::id::{get_unique_prefix_lengths, Id};
 use crate::prop::Prop;
+use crate::query::Query;
 use crate::rpn::StackMachine;
-use crate::tag::Tag;
-use crate::task::{Score, Task};
+use crate::status::Status;
+use crate::tag::{Sign, Tag};
+use crate::task::{Score, Task, TaskDiff};
 use chrono::prelude::*;
+use rand::{thread_rng, RngCore};
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::rc::Rc;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub enum Mutation {
     SetProp(Prop),
     SetTag(Tag),
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-pub enum Query {
-    Id(Id),
-    Tag(Tag),
+    /// adds a note to the task
+    Annotate(String),
+    /// removes every note that contains the text
+    Denotate(String),
+    /// starts working on the task
+    Start,
+    /// stops working on the task
+    Stop,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub enum Command {
-    Create(Vec<Mutation>),
+    /// creates a task, if an id is given and a task with it already exists then that task is
+    /// updated instead, so retrying a create doesn't make duplicates
+    Create(Option<Id>, Vec<Mutation>),
     Read(Vec<Query>),
     Update(Vec<Query>, Vec<Mutation>),
     Delete(Vec<Query>),
+    /// replaces a task with a copy of it that was edited as a whole, or creates it, the copy is
+    /// checked for cycles like any other change
+    Put(Box<Task>),
+}
+
+/// Problems with the tasks that don't stop the engine from running, but that the user should know
+/// about
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub enum EngineWarning {
+    DanglingBlocker { task_id: Rc<Id>, blocker_id: Rc<Id> },
+    DanglingParent { task_id: Rc<Id>, parent_id: Rc<Id> },
+}
+
+impl fmt::Display for EngineWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineWarning::DanglingBlocker {
+                task_id,
+                blocker_id,
+            } => write!(
+                f,
+                "task `{}` is blocked by `{}`, which does not exist",
+                task_id, blocker_id
+            ),
+            EngineWarning::DanglingParent { task_id, parent_id } => write!(
+                f,
+                "task `{}` has the parent `{}`, which does not exist",
+                task_id, parent_id
+            ),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct EngineOutput {
     pub mutated_tasks: Vec<Rc<Task>>,
+    ///tombstones for the deleted tasks, these should be written in place of the originals
+    pub deleted_tasks: Vec<Rc<Task>>,
     pub display_tasks: Vec<(bool, Score, Rc<Task>)>,
+    /// the statuses of the display tasks
+    pub statuses: HashMap<Rc<Id>, Status>,
+    /// the shortest prefix of each display task's id that doesn't match any other task
+    pub short_ids: HashMap<Rc<Id>, String>,
+    pub warnings: Vec<EngineWarning>,
 }
 
 pub struct Engine {
     ///these tasks do not change while the engine runs, and will not be mutated
     all_tasks_collection: HashMap<Rc<Id>, Rc<Task>>,
-    #[allow(dead_code)]
-    dependants_map: HashMap<Rc<Id>, Vec<Rc<Task>>>,
+    dependency_graph: DependencyGraph,
 
+    clear_completed_blockers: bool,
+    force: bool,
+    spawn_recurring_instances: bool,
     filter_machine: StackMachine,
     score_machine: StackMachine,
     now: DateTime<Utc>,
+    /// used to generate the ids of created tasks
+    rng: Box<dyn RngCore>,
 }
 
 impl Engine {
-    /// Creates a new `Engine`, and builds a map of dependants for each task.
+    /// Creates a new `Engine`, and builds the dependency graph of all the tasks.
     pub fn new(
         all_tasks_collection: HashMap<Rc<Id>, Rc<Task>>,
         filter_machine: StackMachine,
         score_machine: StackMachine,
         now: DateTime<Utc>,
     ) -> Self {
-        let mut dependants_map: HashMap<Rc<Id>, Vec<Rc<Task>>> = HashMap::new();
-
-        for (_child_id, child_task) in all_tasks_collection.iter() {
-            if let Some(parent_id) = child_task.get_blocked_by() {
-                dependants_map
-                    .entry(parent_id.clone())
-                    .and_modify(|children: &mut Vec<Rc<Task>>| {
-                        children.push(child_task.clone());
-                    })
-                    .or_insert_with(|| vec![child_task.clone()]);
-            }
-        }
+        let dependency_graph = DependencyGraph::new(&all_tasks_collection);
 
         Self {
             all_tasks_collection,
-            dependants_map,
+            dependency_graph,
 
+            clear_completed_blockers: false,
+            force: false,
+            spawn_recurring_instances: false,
             filter_machine,
             score_machine,
             now,
+            rng: Box::new(thread_rng()),
+        }
+    }
+
+    /// Sets the source of randomness for the ids of created tasks, which is the os rng by default
+    pub fn with_rng(mut self, rng: impl RngCore + 'static) -> Self {
+        self.rng = Box::new(rng);
+        self
+    }
+
+    /// When set, completing a task removes it from the blockers of the tasks it was blocking
+    pub fn with_clear_completed_blockers(mut self, clear_completed_blockers: bool) -> Self {
+        self.clear_completed_blockers = clear_completed_blockers;
+        self
+    }
+
+    /// When set, parents can be done while they still have open children
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// When set, doing a recurring task closes it and creates the next occurrence as a new task,
+    /// rather than moving the task's dates forward
+    pub fn with_spawn_recurring_instances(mut self, spawn_recurring_instances: bool) -> Self {
+        self.spawn_recurring_instances = spawn_recurring_instances;
+        self
+    }
+
+    pub fn get_dependency_graph(&self) -> &DependencyGraph {
+        &self.dependency_graph
+    }
+
+    /// Runs the score and filter programs on a task, returning the score and then the filter
+    /// value, a task is filtered out if its filter value isn't above 0
+    pub fn evaluate_task(&mut self, task: &Task) -> HyperTaskResult<(Score, Score)> {
+        Ok((
+            self.score_machine.run_on(task, &self.dependency_graph)?,
+            self.filter_machine.run_on(task, &self.dependency_graph)?,
+        ))
+    }
+
+    /// Finds the full id of the task that `id` is a prefix of, ids that don't match any task are
+    /// kept as they are, and ids that match more than one task are an error
+    fn resolve_id(&self, id: &Id) -> HyperTaskResult<Id> {
+        if self.all_tasks_collection.contains_key(id) {
+            return Ok(id.clone());
+        }
+
+        let mut candidates: Vec<&Rc<Id>> = self
+            .all_tasks_collection
+            .keys()
+            .filter(|task_id| id.is_prefix_of(task_id))
+            .collect();
+
+        match candidates.len() {
+            0 => Ok(id.clone()),
+            1 => Ok((**candidates[0]).clone()),
+            _ => {
+                candidates.sort_by(|a, b| a.0.cmp(&b.0));
+
+                Err(
+                    HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                        .with_msg(|| {
+                            format!(
+                                "`{}` is ambiguous, it could be any of: {}",
+                                id,
+                                candidates
+                                    .iter()
+                                    .map(|candidate| candidate.to_string())
+                                    .collect::<Vec<String>>()
+                                    .join(" ")
+                            )
+                        }),
+                )
+            }
+        }
+    }
+
+    /// Blockers are stored by their full id, so that we can build the dependency graph
+    fn resolve_mutation_ids(&self, mutations: Vec<Mutation>) -> HyperTaskResult<Vec<Mutation>> {
+        mutations
+            .into_iter()
+            .map(|mutation| {
+                Ok(match mutation {
+                    Mutation::SetProp(Prop::Blocked(ids)) => Mutation::SetProp(Prop::Blocked(
+                        ids.iter()
+                            .map(|id| self.resolve_id(id))
+                            .collect::<HyperTaskResult<Vec<Id>>>()?,
+                    )),
+                    Mutation::SetProp(Prop::Blocker(sign, id)) => {
+                        Mutation::SetProp(Prop::Blocker(sign, self.resolve_id(&id)?))
+                    }
+                    Mutation::SetProp(Prop::Parent(Some(id))) => {
+                        Mutation::SetProp(Prop::Parent(Some(self.resolve_id(&id)?)))
+                    }
+                    mutation => mutation,
+                })
+            })
+            .collect()
+    }
+
+    /// Stops commands that change tasks from changing every task that an ambiguous id matches
+    fn check_for_ambiguous_ids(&self, queries: &[Query]) -> HyperTaskResult<()> {
+        for query in queries {
+            match query {
+                Query::Id(id)
+                | Query::BlockedBy(id)
+                | Query::Parent(id)
+                | Query::Subtree(id)
+                | Query::Template(id) => {
+                    self.resolve_id(id)?;
+                }
+                Query::And(queries) | Query::Or(queries) => {
+                    self.check_for_ambiguous_ids(queries)?
+                }
+                // an ambiguous id can only exclude more tasks than the user meant
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A task can only see its own parent, so subtree queries are expanded into the ids of
+    /// every task in the subtree
+    fn resolve_queries(&self, queries: Vec<Query>) -> Vec<Query> {
+        queries
+            .into_iter()
+            .map(|query| self.resolve_query(query))
+            .collect()
+    }
+
+    fn resolve_query(&self, query: Query) -> Query {
+        match query {
+            Query::Id(id) if self.all_tasks_collection.contains_key(&id) => Query::ExactId(id),
+            Query::Subtree(id) => {
+                let is_exact = self.all_tasks_collection.contains_key(&id);
+                let mut ids: HashSet<Rc<Id>> = HashSet::new();
+
+                for root_id in self.all_tasks_collection.keys().filter(|task_id| {
+                    if is_exact {
+                        ***task_id == id
+                    } else {
+                        id.is_prefix_of(task_id)
+                    }
+                }) {
+                    ids.insert(root_id.clone());
+                    ids.extend(self.dependency_graph.get_descendants(root_id));
+                }
+
+                Query::Or(
+                    ids.iter()
+                        .map(|id| Query::ExactId((**id).clone()))
+                        .collect(),
+                )
+            }
+            Query::Status(status) => Query::Or(
+                self.all_tasks_collection
+                    .values()
+                    .filter(|task| Status::of(task, &self.dependency_graph, &self.now) == status)
+                    .map(|task| Query::ExactId((*task.get_id()).clone()))
+                    .collect(),
+            ),
+            Query::And(queries) => Query::And(self.resolve_queries(queries)),
+            Query::Or(queries) => Query::Or(self.resolve_queries(queries)),
+            Query::Not(query) => Query::Not(Box::new(self.resolve_query(*query))),
+            query => query,
+        }
+    }
+
+    /// Stops parents from being done while any of their children are still open
+    fn check_for_open_children(
+        &self,
+        mutated_tasks: &[Rc<Task>],
+        next_dependency_graph: &DependencyGraph,
+    ) -> HyperTaskResult<()> {
+        for task in mutated_tasks {
+            let was_done = self
+                .all_tasks_collection
+                .get(&task.get_id())
+                .map(|original| original.get_done().is_some())
+                .unwrap_or(false);
+
+            if task.get_done().is_none() || was_done {
+                continue;
+            }
+
+            let mut open_children: Vec<String> = next_dependency_graph
+                .get_open_children(&task.get_id())
+                .map(|child| child.get_id().to_string())
+                .collect();
+
+            if !open_children.is_empty() {
+                open_children.sort();
+
+                return Err(HyperTaskError::new(
+                    HyperTaskErrorDomain::Mutation,
+                    HyperTaskErrorAction::Run,
+                )
+                .with_msg(|| {
+                    format!(
+                        "`{}` can't be done while it has open children: {}",
+                        task.get_id(),
+                        open_children.join(" ")
+                    )
+                }));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn clear_completed_blockers_from_dependants(&self, mutated_tasks: &mut Vec<Rc<Task>>) {
+        let completed_ids: Vec<Rc<Id>> = mutated_tasks
+            .iter()
+            .filter(|task| task.get_done().is_some())
+            .map(|task| task.get_id())
+            .collect();
+
+        for completed_id in completed_ids {
+            let mutations = [Mutation::SetProp(Prop::Blocker(
+                Sign::Minus,
+                (*completed_id).clone(),
+            ))];
+
+            for dependant in self.dependency_graph.get_dependants(&completed_id) {
+                // the dependant might have already been mutated by this command
+                match mutated_tasks
+                    .iter()
+                    .position(|task| task.get_id() == dependant.get_id())
+                {
+                    Some(index) => {
+                        let updated_task =
+                            mutated_tasks[index].apply_mutations(&mutations, &self.now);
+                        mutated_tasks[index] = Rc::new(updated_task);
+                    }
+                    None => {
+                        mutated_tasks
+                            .push(Rc::new(dependant.apply_mutations(&mutations, &self.now)));
+                    }
+                }
+            }
+        }
+    }
+
+    fn restore_tasks_collection(&mut self, all_tasks_collection: HashMap<Rc<Id>, Rc<Task>>) {
+        self.dependency_graph = DependencyGraph::new(&all_tasks_collection);
+        self.all_tasks_collection = all_tasks_collection;
+    }
+
+    /// Runs the commands like `run_batch`, but doesn't keep any of the changes, and returns a
+    /// diff of every task the commands would change
+    pub fn preview(&mut self, commands: Vec<Command>) -> HyperTaskResult<Vec<TaskDiff>> {
+        let original_tasks_collection = self.all_tasks_collection.clone();
+
+        let output = self.run_batch(commands);
+        self.restore_tasks_collection(original_tasks_collection.clone());
+
+        let EngineOutput {
+            mutated_tasks,
+            deleted_tasks,
+            ..
+        } = output?;
+
+        Ok(mutated_tasks
+            .iter()
+            .chain(deleted_tasks.iter())
+            .map(|task| {
+                TaskDiff::new(
+                    original_tasks_collection
+                        .get(&task.get_id())
+                        .map(|original| &**original),
+                    task,
+                )
+            })
+            .collect())
+    }
+
+    /// Runs every command in order, as if each was run on its own. If any of them fails then
+    /// none of them are applied, otherwise the outputs are combined, keeping the latest version
+    /// of each task
+    pub fn run_batch(&mut self, commands: Vec<Command>) -> HyperTaskResult<EngineOutput> {
+        let original_tasks_collection = self.all_tasks_collection.clone();
+
+        let mut mutated_tasks: Vec<Rc<Task>> = vec![];
+        let mut deleted_tasks: Vec<Rc<Task>> = vec![];
+        let mut display_tasks: Vec<(bool, Score, Rc<Task>)> = vec![];
+        let mut statuses: HashMap<Rc<Id>, Status> = HashMap::new();
+        let mut short_ids: HashMap<Rc<Id>, String> = HashMap::new();
+        let mut warnings: Vec<EngineWarning> = vec![];
+
+        for command in commands {
+            let output = match self.run(command) {
+                Ok(output) => output,
+                Err(e) => {
+                    self.restore_tasks_collection(original_tasks_collection);
+
+                    return Err(e);
+                }
+            };
+
+            let changed_ids: HashSet<Rc<Id>> = output
+                .mutated_tasks
+                .iter()
+                .chain(output.deleted_tasks.iter())
+                .map(|task| task.get_id())
+                .collect();
+
+            mutated_tasks.retain(|task| !changed_ids.contains(&task.get_id()));
+            display_tasks.retain(|(_, _, task)| !changed_ids.contains(&task.get_id()));
+
+            mutated_tasks.extend(output.mutated_tasks);
+            deleted_tasks.extend(output.deleted_tasks);
+            statuses.extend(output.statuses);
+            short_ids.extend(output.short_ids);
+
+            for warning in output.warnings {
+                if !warnings.contains(&warning) {
+                    warnings.push(warning);
+                }
+            }
+
+            for display_task in output.display_tasks {
+                if !display_tasks
+                    .iter()
+                    .any(|(_, _, task)| task.get_id() == display_task.2.get_id())
+                {
+                    display_tasks.push(display_task);
+                }
+            }
         }
+
+        // tasks that were created and then deleted by the batch are only written as tombstones
+        mutated_tasks.retain(|task| {
+            !deleted_tasks
+                .iter()
+                .any(|deleted_task| deleted_task.get_id() == task.get_id())
+        });
+        display_tasks.sort_by(|(_, a, _), (_, b, _)| b.partial_cmp(a).unwrap());
+
+        Ok(EngineOutput {
+            mutated_tasks,
+            deleted_tasks,
+            display_tasks,
+            statuses,
+            short_ids,
+            warnings,
+        })
     }
 
     pub fn run(&mut self, command: Command) -> HyperTaskResult<EngineOutput> {
         let mut mutated_tasks: Vec<Rc<Task>> = vec![];
+        let mut deleted_tasks: Vec<Rc<Task>> = vec![];
         let mut display_ids: HashSet<Rc<Id>> = HashSet::new();
 
         match command {
             //actually perform mutations
-            Command::Create(mutations) => {
-                let new_task: Rc<Task> =
-                    Rc::new(Task::generate(&self.now).apply_mutations(&mutations, &self.now));
+            Command::Create(id, mutations) => {
+                let mutations = self.resolve_mutation_ids(mutations)?;
+
+                let task: Task = match id {
+                    Some(id) => {
+                        id.validate()?;
+
+                        match self.all_tasks_collection.get(&id) {
+                            Some(existing_task) => (**existing_task).clone(),
+                            None => Task::new(id, &self.now),
+                        }
+                    }
+                    None => Task::new(Id::generate_with(&mut self.rng), &self.now),
+                };
+
+                let new_task: Rc<Task> = Rc::new(task.apply_mutations(&mutations, &self.now));
                 let id = new_task.get_id();
 
-                self.all_tasks_collection
-                    .insert(id.clone(), new_task.clone());
                 mutated_tasks.push(new_task);
                 display_ids.insert(id);
             }
+            Command::Put(task) => {
+                task.get_id().validate()?;
+
+                let id = task.get_id();
+
+                mutated_tasks.push(Rc::new(*task));
+                display_ids.insert(id);
+            }
             Command::Update(query, mutation) => {
+                self.check_for_ambiguous_ids(&query)?;
+
+                let query = self.resolve_queries(query);
+                let mutation = self.resolve_mutation_ids(mutation)?;
+
                 for (id, task) in self.all_tasks_collection.iter() {
                     // don't run mutations on tasks that are filtered out, the user probably
                     // didn't mean to
                     if task.satisfies_queries(&query)
-                        && self.filter_machine.run_on(&task, &self.dependants_map)? > 0.0
+                        && self.filter_machine.run_on(&task, &self.dependency_graph)? > 0.0
                     {
-                        let updated_task: Task = task.apply_mutations(&mutation, &self.now);
+                        if self.spawn_recurring_instances {
+                            let (updated_task, next_instance) =
+                                task.apply_mutations_spawning_instances(&mutation, &self.now);
+
+                            // another device may have already done this occurrence
+                            if let Some(next_instance) = next_instance.filter(|next_instance| {
+                                !self
+                                    .all_tasks_collection
+                                    .contains_key(&next_instance.get_id())
+                            }) {
+                                display_ids.insert(next_instance.get_id());
+                                mutated_tasks.push(Rc::new(next_instance));
+                            }
+
+                            mutated_tasks.push(Rc::new(updated_task));
+                        } else {
+                            let updated_task: Task = task.apply_mutations(&mutation, &self.now);
+
+                            mutated_tasks.push(Rc::new(updated_task));
+                        }
 
-                        mutated_tasks.push(Rc::new(updated_task));
                         display_ids.insert(id.clone());
                     }
                 }
+
+                if self.clear_completed_blockers {
+                    self.clear_completed_blockers_from_dependants(&mut mutated_tasks);
+                }
+            }
+            Command::Delete(query) => {
+                self.check_for_ambiguous_ids(&query)?;
+
+                let query = self.resolve_queries(query);
+
+                for task in self.all_tasks_collection.values() {
+                    // same as with updates, only delete tasks that the user can see
+                    if task.satisfies_queries(&query)
+                        && self.filter_machine.run_on(task, &self.dependency_graph)? > 0.0
+                    {
+                        deleted_tasks.push(Rc::new(task.to_tombstone(&self.now)));
+                    }
+                }
             }
 
             //if we're just querying, run the query
             Command::Read(query) => {
+                let query = self.resolve_queries(query);
+
                 for (id, task) in self.all_tasks_collection.iter() {
                     // if there's any query specified
                     if !query.is_empty() {
@@ -118,21 +572,75 @@ impl Engine {
                         };
                     } else {
                         //otherwise, filter out queries that don't satisfy the filter
-                        if self.filter_machine.run_on(&task, &self.dependants_map)? > 0.0 {
+                        if self.filter_machine.run_on(&task, &self.dependency_graph)? > 0.0 {
                             display_ids.insert(id.clone());
                         }
                     }
                 }
             }
-            _ => {}
         };
 
+        let mut next_tasks_collection = self.all_tasks_collection.clone();
+
         for task in &mutated_tasks {
-            self.all_tasks_collection
-                .insert(task.get_id(), task.clone());
+            next_tasks_collection.insert(task.get_id(), task.clone());
+        }
+
+        for task in &deleted_tasks {
+            next_tasks_collection.remove(&task.get_id());
+        }
+
+        let next_dependency_graph = DependencyGraph::new(&next_tasks_collection);
+
+        for task in &mutated_tasks {
+            if let Some(cycle) = next_dependency_graph.find_cycle(&task.get_id()) {
+                return Err(HyperTaskError::new(
+                    HyperTaskErrorDomain::Mutation,
+                    HyperTaskErrorAction::Run,
+                )
+                .with_msg(|| {
+                    format!(
+                        "blocking would create a dependency cycle: {}",
+                        cycle
+                            .iter()
+                            .map(|id| id.to_string())
+                            .collect::<Vec<String>>()
+                            .join(" -> ")
+                    )
+                }));
+            }
+
+            if let Some(cycle) = next_dependency_graph.find_parent_cycle(&task.get_id()) {
+                return Err(HyperTaskError::new(
+                    HyperTaskErrorDomain::Mutation,
+                    HyperTaskErrorAction::Run,
+                )
+                .with_msg(|| {
+                    format!(
+                        "parenting would create a cycle: {}",
+                        cycle
+                            .iter()
+                            .map(|id| id.to_string())
+                            .collect::<Vec<String>>()
+                            .join(" -> ")
+                    )
+                }));
+            }
+        }
+
+        if !self.force {
+            self.check_for_open_children(&mutated_tasks, &next_dependency_graph)?;
         }
 
+        self.all_tasks_collection = next_tasks_collection;
+        self.dependency_graph = next_dependency_graph;
+
         let mut display_tasks: Vec<(bool, Score, Rc<Task>)> = Vec::with_capacity(display_ids.len());
+        let mut statuses: HashMap<Rc<Id>, Status> = HashMap::new();
+        let mut short_ids: HashMap<Rc<Id>, String> = HashMap::new();
+        let mut warnings: Vec<EngineWarning> = vec![];
+
+        let unique_prefix_lengths = get_unique_prefix_lengths(self.all_tasks_collection.keys());
 
         for id in display_ids.into_iter() {
             let task: Rc<Task> = self
@@ -141,9 +649,33 @@ impl Engine {
                 .expect("if I have the Id, I should have the Task")
                 .clone();
 
-            let score = self.score_machine.run_on(&task, &self.dependants_map)?;
-            let filter = self.filter_machine.run_on(&task, &self.dependants_map)?;
+            let score = self.score_machine.run_on(&task, &self.dependency_graph)?;
+            let filter = self.filter_machine.run_on(&task, &self.dependency_graph)?;
+
+            for blocker_id in self.dependency_graph.get_dangling_blockers(&task) {
+                warnings.push(EngineWarning::DanglingBlocker {
+                    task_id: task.get_id(),
+                    blocker_id: blocker_id.clone(),
+                });
+            }
+
+            if let Some(parent_id) = self.dependency_graph.get_dangling_parent(&task) {
+                warnings.push(EngineWarning::DanglingParent {
+                    task_id: task.get_id(),
+                    parent_id: parent_id.clone(),
+                });
+            }
 
+            statuses.insert(
+                task.get_id(),
+                Status::of(&task, &self.dependency_graph, &self.now),
+            );
+            short_ids.insert(
+                task.get_id(),
+                task.get_id()
+                    .prefix(unique_prefix_lengths[&task.get_id()])
+                    .to_string(),
+            );
             display_tasks.push((filter > 0.0, score, task));
         }
 
@@ -151,7 +683,781 @@ impl Engine {
 
         Ok(EngineOutput {
             mutated_tasks,
+            deleted_tasks,
             display_tasks,
+            statuses,
+            short_ids,
+            warnings,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpn::RPNSymbol;
+
+    fn create_engine(tasks: Vec<Task>) -> Engine {
+        let now = Utc.ymd(2015, 11, 15).and_hms(9, 10, 11);
+
+        Engine::new(
+            tasks
+                .into_iter()
+                .map(|task| (task.get_id(), Rc::new(task)))
+                .collect(),
+            StackMachine::new(RPNSymbol::parse_program("1"), HashMap::new()),
+            StackMachine::new(RPNSymbol::parse_program("1"), HashMap::new()),
+            now,
+        )
+    }
+
+    fn create_task(id: &str) -> Task {
+        create_blocked_task(id, &[])
+    }
+
+    fn create_blocked_task(id: &str, blocked_by: &[&str]) -> Task {
+        serde_json::from_str(&format!(
+            r#"{{
+                "created_at": "2015-11-15T09:10:11Z",
+                "updated_at": "2015-11-15T09:10:11Z",
+                "id": "{}",
+                "blocked_by": {}
+            }}"#,
+            id,
+            serde_json::to_string(blocked_by).unwrap()
+        ))
+        .unwrap()
+    }
+
+    fn create_child_task(id: &str, parent: &str) -> Task {
+        serde_json::from_str(&format!(
+            r#"{{
+                "created_at": "2015-11-15T09:10:11Z",
+                "updated_at": "2015-11-15T09:10:11Z",
+                "id": "{}",
+                "parent": "{}"
+            }}"#,
+            id, parent
+        ))
+        .unwrap()
+    }
+
+    mod delete {
+        use super::*;
+
+        #[test]
+        fn replaces_matching_tasks_with_tombstones() {
+            let mut engine = create_engine(vec![create_task("aaaa"), create_task("bbbb")]);
+
+            let EngineOutput {
+                mutated_tasks,
+                deleted_tasks,
+                ..
+            } = engine
+                .run(Command::Delete(vec![Query::Id(Id("aaaa".to_owned()))]))
+                .unwrap();
+
+            assert!(mutated_tasks.is_empty());
+            assert_eq!(deleted_tasks.len(), 1);
+            assert_eq!(deleted_tasks[0].get_id(), Rc::new(Id("aaaa".to_owned())));
+            assert!(deleted_tasks[0].is_tombstone());
+
+            let EngineOutput { display_tasks, .. } = engine.run(Command::Read(vec![])).unwrap();
+
+            assert_eq!(display_tasks.len(), 1);
+            assert_eq!(display_tasks[0].2.get_id(), Rc::new(Id("bbbb".to_owned())));
+        }
+    }
+
+    mod dependencies {
+        use super::*;
+        use crate::dependencies::TransitiveMetrics;
+
+        #[test]
+        fn maps_every_blocker_to_its_dependants() {
+            let engine = create_engine(vec![
+                create_task("aaaa"),
+                create_task("bbbb"),
+                create_blocked_task("cccc", &["aaaa", "bbbb"]),
+            ]);
+
+            for id in &["aaaa", "bbbb"] {
+                let dependants = engine.dependency_graph.get_dependants(&Id(id.to_string()));
+
+                assert_eq!(dependants.len(), 1);
+                assert_eq!(dependants[0].get_id(), Rc::new(Id("cccc".to_owned())));
+            }
+        }
+
+        #[test]
+        fn loads_tasks_with_a_single_blocker() {
+            let task: Task = serde_json::from_str(
+                r#"{
+                    "created_at": "2015-11-15T09:10:11Z",
+                    "updated_at": "2015-11-15T09:10:11Z",
+                    "id": "bbbb",
+                    "blocked_by": "aaaa"
+                }"#,
+            )
+            .unwrap();
+
+            assert_eq!(task, create_blocked_task("bbbb", &["aaaa"]));
+        }
+
+        #[test]
+        fn resolves_partial_blocker_ids() {
+            let mut engine = create_engine(vec![create_task("aaaa"), create_task("bbbb")]);
+
+            let EngineOutput { mutated_tasks, .. } = engine
+                .run(Command::Update(
+                    vec![Query::Id(Id("aaaa".to_owned()))],
+                    vec![Mutation::SetProp(Prop::Blocker(
+                        Sign::Plus,
+                        Id("bb".to_owned()),
+                    ))],
+                ))
+                .unwrap();
+
+            assert!(mutated_tasks[0]
+                .get_blocked_by()
+                .contains(&Id("bbbb".to_owned())));
+        }
+
+        #[test]
+        fn errors_when_mutation_would_create_a_cycle() {
+            let mut engine = create_engine(vec![
+                create_task("aaaa"),
+                create_blocked_task("bbbb", &["aaaa"]),
+                create_blocked_task("cccc", &["bbbb"]),
+            ]);
+
+            let result = engine.run(Command::Update(
+                vec![Query::Id(Id("aaaa".to_owned()))],
+                vec![Mutation::SetProp(Prop::Blocker(
+                    Sign::Plus,
+                    Id("cccc".to_owned()),
+                ))],
+            ));
+
+            assert_eq!(
+                result.err(),
+                Some(HyperTaskError::new(
+                    HyperTaskErrorDomain::Mutation,
+                    HyperTaskErrorAction::Run
+                ))
+            );
+
+            let EngineOutput { display_tasks, .. } = engine
+                .run(Command::Read(vec![Query::Id(Id("aaaa".to_owned()))]))
+                .unwrap();
+
+            assert!(display_tasks[0].2.get_blocked_by().is_empty());
+        }
+
+        #[test]
+        fn errors_when_an_edited_task_would_create_a_cycle() {
+            let mut engine = create_engine(vec![
+                create_task("aaaa"),
+                create_blocked_task("bbbb", &["aaaa"]),
+            ]);
+
+            let result = engine.run(Command::Put(Box::new(create_blocked_task(
+                "aaaa",
+                &["bbbb"],
+            ))));
+
+            assert_eq!(
+                result.err(),
+                Some(HyperTaskError::new(
+                    HyperTaskErrorDomain::Mutation,
+                    HyperTaskErrorAction::Run
+                ))
+            );
+
+            let EngineOutput { mutated_tasks, .. } = engine
+                .run(Command::Put(Box::new(create_blocked_task(
+                    "cccc",
+                    &["bbbb"],
+                ))))
+                .unwrap();
+
+            assert_eq!(mutated_tasks.len(), 1);
+        }
+
+        #[test]
+        fn only_counts_open_blockers() {
+            let mut engine = create_engine(vec![
+                create_task("aaaa"),
+                create_task("bbbb"),
+                create_task("eeee"),
+                create_blocked_task("cccc", &["aaaa", "bbbb", "dddd", "eeee"]),
+            ]);
+
+            engine
+                .run(Command::Update(
+                    vec![Query::Id(Id("aaaa".to_owned()))],
+                    vec![Mutation::SetProp(Prop::Done(engine.now))],
+                ))
+                .unwrap();
+
+            let task = create_blocked_task("cccc", &["aaaa", "bbbb", "dddd", "eeee"]);
+            let run_program = |program: &str| {
+                StackMachine::new(RPNSymbol::parse_program(program), HashMap::new())
+                    .run_on(&task, &engine.dependency_graph)
+                    .unwrap()
+            };
+
+            assert_eq!(run_program("blocked :"), 1.0);
+            assert_eq!(run_program("blocker_count :"), 2.0);
+        }
+
+        #[test]
+        fn warns_about_dangling_blockers() {
+            let mut engine = create_engine(vec![create_blocked_task("bbbb", &["aaaa"])]);
+
+            let EngineOutput { warnings, .. } = engine
+                .run(Command::Read(vec![Query::Id(Id("bbbb".to_owned()))]))
+                .unwrap();
+
+            assert_eq!(
+                warnings,
+                vec![EngineWarning::DanglingBlocker {
+                    task_id: Rc::new(Id("bbbb".to_owned())),
+                    blocker_id: Rc::new(Id("aaaa".to_owned())),
+                }]
+            );
+        }
+
+        #[test]
+        fn clears_completed_blockers_when_enabled() {
+            let mut engine = create_engine(vec![
+                create_task("aaaa"),
+                create_blocked_task("bbbb", &["aaaa"]),
+            ])
+            .with_clear_completed_blockers(true);
+
+            let EngineOutput { mutated_tasks, .. } = engine
+                .run(Command::Update(
+                    vec![Query::Id(Id("aaaa".to_owned()))],
+                    vec![Mutation::SetProp(Prop::Done(engine.now))],
+                ))
+                .unwrap();
+
+            assert_eq!(mutated_tasks.len(), 2);
+            assert!(mutated_tasks[1].get_blocked_by().is_empty());
+        }
+
+        #[test]
+        fn follows_open_dependants_down_the_chain() {
+            let mut engine = create_engine(vec![
+                create_task("aaaa"),
+                create_blocked_task("bbbb", &["aaaa"]),
+                create_blocked_task("cccc", &["aaaa"]),
+                create_blocked_task("dddd", &["bbbb", "cccc"]),
+                create_blocked_task("eeee", &["dddd"]),
+            ]);
+
+            let due = Utc.ymd(2015, 11, 16).and_hms(9, 10, 11);
+
+            engine
+                .run(Command::Update(
+                    vec![Query::Id(Id("eeee".to_owned()))],
+                    vec![Mutation::SetProp(Prop::Due(Some(due)))],
+                ))
+                .unwrap();
+
+            assert_eq!(
+                engine
+                    .dependency_graph
+                    .get_transitive_metrics(&Id("aaaa".to_owned())),
+                Some(TransitiveMetrics {
+                    dependants: 4,
+                    earliest_due: Some(due),
+                    depth: 3,
+                })
+            );
+        }
+
+        #[test]
+        fn follows_long_chains_of_blockers() {
+            let chain_length = 20_000;
+            let mut tasks = vec![create_task("t0")];
+            for i in 1..chain_length {
+                tasks.push(create_blocked_task(
+                    &format!("t{}", i),
+                    &[&format!("t{}", i - 1)],
+                ));
+            }
+
+            let mut engine = create_engine(tasks);
+
+            assert_eq!(
+                engine
+                    .dependency_graph
+                    .get_transitive_metrics(&Id("t0".to_owned()))
+                    .map(|metrics| metrics.depth),
+                Some(chain_length - 1)
+            );
+
+            let result = engine.run(Command::Update(
+                vec![Query::Id(Id("t0".to_owned()))],
+                vec![Mutation::SetProp(Prop::Blocker(
+                    Sign::Plus,
+                    Id(format!("t{}", chain_length - 1)),
+                ))],
+            ));
+            assert!(result.is_err());
+        }
+    }
+
+    mod hierarchy {
+        use super::*;
+
+        fn create_epic_engine() -> Engine {
+            create_engine(vec![
+                create_task("aaaa"),
+                create_child_task("bbbb", "aaaa"),
+                create_child_task("cccc", "aaaa"),
+                create_child_task("dddd", "cccc"),
+                create_task("eeee"),
+            ])
+        }
+
+        #[test]
+        fn subtree_query_matches_every_descendant() {
+            let mut engine = create_epic_engine();
+
+            let EngineOutput { display_tasks, .. } = engine
+                .run(Command::Read(vec![Query::Subtree(Id("aaaa".to_owned()))]))
+                .unwrap();
+
+            let mut ids: Vec<String> = display_tasks
+                .iter()
+                .map(|(_, _, task)| task.get_id().to_string())
+                .collect();
+            ids.sort();
+
+            assert_eq!(ids, vec!["aaaa", "bbbb", "cccc", "dddd"]);
+        }
+
+        #[test]
+        fn progress_counts_done_descendants() {
+            let mut engine = create_epic_engine();
+
+            engine
+                .run(Command::Update(
+                    vec![Query::Id(Id("dddd".to_owned()))],
+                    vec![Mutation::SetProp(Prop::Done(engine.now))],
+                ))
+                .unwrap();
+
+            assert_eq!(
+                engine.dependency_graph.get_progress(&Id("aaaa".to_owned())),
+                Some(100.0 / 3.0)
+            );
+            assert_eq!(
+                engine.dependency_graph.get_progress(&Id("eeee".to_owned())),
+                None
+            );
+        }
+
+        #[test]
+        fn errors_when_done_with_open_children_unless_forced() {
+            let mut engine = create_epic_engine();
+
+            let result = engine.run(Command::Update(
+                vec![Query::Id(Id("cccc".to_owned()))],
+                vec![Mutation::SetProp(Prop::Done(engine.now))],
+            ));
+
+            assert!(result.is_err());
+
+            let mut engine = create_epic_engine().with_force(true);
+
+            let EngineOutput { mutated_tasks, .. } = engine
+                .run(Command::Update(
+                    vec![Query::Id(Id("cccc".to_owned()))],
+                    vec![Mutation::SetProp(Prop::Done(engine.now))],
+                ))
+                .unwrap();
+
+            assert!(mutated_tasks[0].get_done().is_some());
+        }
+
+        #[test]
+        fn errors_when_mutation_would_create_a_cycle() {
+            let mut engine = create_epic_engine();
+
+            let result = engine.run(Command::Update(
+                vec![Query::Id(Id("aaaa".to_owned()))],
+                vec![Mutation::SetProp(Prop::Parent(Some(Id("dd".to_owned()))))],
+            ));
+
+            assert!(result.is_err());
+        }
+    }
+
+    mod recurrence {
+        use super::*;
+
+        fn create_recurring_task(id: &str) -> Task {
+            serde_json::from_str(&format!(
+                r#"{{
+                    "created_at": "2015-11-15T09:10:11Z",
+                    "updated_at": "2015-11-15T09:10:11Z",
+                    "id": "{}",
+                    "due": "2015-11-15T09:00:00Z",
+                    "recur": {{ "Day": 1 }}
+                }}"#,
+                id
+            ))
+            .unwrap()
+        }
+
+        fn complete(engine: &mut Engine, id: &str) -> Vec<Rc<Task>> {
+            engine
+                .run(Command::Update(
+                    vec![Query::Id(Id(id.to_owned()))],
+                    vec![Mutation::SetProp(Prop::Done(engine.now))],
+                ))
+                .unwrap()
+                .mutated_tasks
+        }
+
+        #[test]
+        fn keeps_done_occurrences_when_spawning_instances() {
+            let mut engine = create_engine(vec![create_recurring_task("aaaa")])
+                .with_spawn_recurring_instances(true);
+
+            let mutated_tasks = complete(&mut engine, "aaaa");
+            assert_eq!(mutated_tasks.len(), 2);
+
+            let next = mutated_tasks
+                .iter()
+                .find(|task| task.get_done().is_none())
+                .unwrap()
+                .clone();
+            assert!(next.get_due().unwrap() > Utc.ymd(2015, 11, 15).and_hms(9, 0, 0));
+
+            complete(&mut engine, &next.get_id().to_string());
+
+            let EngineOutput { display_tasks, .. } = engine
+                .run(Command::Read(vec![Query::Template(Id("aaaa".to_owned()))]))
+                .unwrap();
+
+            assert_eq!(display_tasks.len(), 3);
+            assert_eq!(
+                display_tasks
+                    .iter()
+                    .filter(|(_, _, task)| task.get_done().is_some())
+                    .count(),
+                2
+            );
+        }
+
+        #[test]
+        fn gives_the_same_occurrence_the_same_id() {
+            let first = complete(
+                &mut create_engine(vec![create_recurring_task("aaaa")])
+                    .with_spawn_recurring_instances(true),
+                "aaaa",
+            );
+            let second = complete(
+                &mut create_engine(vec![create_recurring_task("aaaa")])
+                    .with_spawn_recurring_instances(true),
+                "aaaa",
+            );
+
+            let next_id = |tasks: &[Rc<Task>]| {
+                tasks
+                    .iter()
+                    .find(|task| task.get_done().is_none())
+                    .map(|task| task.get_id())
+            };
+
+            assert_eq!(next_id(&first), next_id(&second));
+        }
+
+        #[test]
+        fn gives_the_same_id_when_done_at_different_times_on_each_device() {
+            let task: Task = serde_json::from_str(
+                r#"{
+                    "created_at": "2015-11-15T09:10:11Z",
+                    "updated_at": "2015-11-15T09:10:11Z",
+                    "id": "aaaa",
+                    "due": "2015-11-15T09:00:00Z",
+                    "recur": { "frequency": "Day", "interval": 3, "anchor": "Done" }
+                }"#,
+            )
+            .unwrap();
+
+            let complete_at = |now: DateTime<Utc>| {
+                let mut engine = Engine::new(
+                    vec![(task.get_id(), Rc::new(task.clone()))]
+                        .into_iter()
+                        .collect(),
+                    StackMachine::new(RPNSymbol::parse_program("1"), HashMap::new()),
+                    StackMachine::new(RPNSymbol::parse_program("1"), HashMap::new()),
+                    now,
+                )
+                .with_spawn_recurring_instances(true);
+
+                complete(&mut engine, "aaaa")
+                    .into_iter()
+                    .find(|task| task.get_done().is_none())
+                    .unwrap()
+            };
+
+            let first = complete_at(Utc.ymd(2015, 11, 15).and_hms(10, 0, 0));
+            let second = complete_at(Utc.ymd(2015, 11, 16).and_hms(18, 0, 0));
+
+            assert_ne!(first.get_due(), second.get_due());
+            assert_eq!(first.get_id(), second.get_id());
+        }
+    }
+
+    mod status {
+        use super::*;
+
+        #[test]
+        fn derives_status_from_fields_and_now() {
+            let waiting: Task = serde_json::from_str(
+                r#"{
+                    "created_at": "2015-11-15T09:10:11Z",
+                    "updated_at": "2015-11-15T09:10:11Z",
+                    "id": "cccc",
+                    "wait": "2015-11-16T09:10:11Z"
+                }"#,
+            )
+            .unwrap();
+
+            let mut engine = create_engine(vec![
+                create_task("aaaa"),
+                create_blocked_task("bbbb", &["aaaa"]),
+                waiting,
+            ]);
+
+            let EngineOutput { statuses, .. } = engine
+                .run(Command::Read(vec![Query::Or(vec![
+                    Query::Id(Id("aaaa".to_owned())),
+                    Query::Id(Id("bbbb".to_owned())),
+                    Query::Id(Id("cccc".to_owned())),
+                ])]))
+                .unwrap();
+
+            let status_of = |id: &str| statuses[&Rc::new(Id(id.to_owned()))];
+            assert_eq!(status_of("aaaa"), Status::Pending);
+            assert_eq!(status_of("bbbb"), Status::Blocked);
+            assert_eq!(status_of("cccc"), Status::Waiting);
+
+            let EngineOutput { display_tasks, .. } = engine
+                .run(Command::Read(vec![Query::Status(Status::Waiting)]))
+                .unwrap();
+
+            assert_eq!(display_tasks.len(), 1);
+            assert_eq!(display_tasks[0].2.get_id(), Rc::new(Id("cccc".to_owned())));
+        }
+    }
+
+    mod batch {
+        use super::*;
+        use crate::query::QueryAttribute;
+        use crate::task::FieldChange;
+
+        fn block(id: &str, blocker: &str) -> Command {
+            Command::Update(
+                vec![Query::Id(Id(id.to_owned()))],
+                vec![Mutation::SetProp(Prop::Blocker(
+                    Sign::Plus,
+                    Id(blocker.to_owned()),
+                ))],
+            )
+        }
+
+        #[test]
+        fn combines_the_output_of_every_command() {
+            let mut engine = create_engine(vec![create_task("aaaa"), create_task("bbbb")]);
+
+            let EngineOutput {
+                mutated_tasks,
+                deleted_tasks,
+                ..
+            } = engine
+                .run_batch(vec![
+                    block("bbbb", "aaaa"),
+                    Command::Update(
+                        vec![Query::Id(Id("bbbb".to_owned()))],
+                        vec![Mutation::Annotate("blocked".to_owned())],
+                    ),
+                    Command::Delete(vec![Query::Id(Id("aaaa".to_owned()))]),
+                ])
+                .unwrap();
+
+            assert_eq!(mutated_tasks.len(), 1);
+            assert_eq!(mutated_tasks[0].get_blocked_by().len(), 1);
+            assert_eq!(mutated_tasks[0].get_annotations().len(), 1);
+            assert_eq!(deleted_tasks.len(), 1);
+        }
+
+        #[test]
+        fn applies_nothing_when_a_command_fails() {
+            let mut engine = create_engine(vec![create_task("aaaa"), create_task("bbbb")]);
+
+            let result = engine.run_batch(vec![block("bbbb", "aaaa"), block("aaaa", "bbbb")]);
+            assert!(result.is_err());
+
+            let EngineOutput { display_tasks, .. } = engine
+                .run(Command::Read(vec![Query::Has(QueryAttribute::Blocked)]))
+                .unwrap();
+            assert!(display_tasks.is_empty());
+        }
+
+        #[test]
+        fn previews_changes_without_applying_them() {
+            let mut engine = create_engine(vec![create_task("aaaa"), create_task("bbbb")]);
+            let original_tasks_collection = engine.all_tasks_collection.clone();
+
+            let diffs = engine.preview(vec![block("bbbb", "aaaa")]).unwrap();
+
+            assert_eq!(
+                diffs,
+                vec![TaskDiff {
+                    id: "bbbb".to_owned(),
+                    is_new: false,
+                    changes: vec![FieldChange {
+                        field: "blocked".to_owned(),
+                        before: None,
+                        after: Some("aaaa".to_owned()),
+                    }],
+                }]
+            );
+            assert_eq!(engine.all_tasks_collection, original_tasks_collection);
+            assert!(engine
+                .dependency_graph
+                .get_dependants(&Id("aaaa".to_owned()))
+                .is_empty());
+        }
+    }
+
+    mod ids {
+        use super::*;
+
+        fn done(prefix: &str) -> Command {
+            Command::Update(
+                vec![Query::Id(Id(prefix.to_owned()))],
+                vec![Mutation::SetProp(Prop::Done(
+                    Utc.ymd(2015, 11, 15).and_hms(9, 10, 11),
+                ))],
+            )
+        }
+
+        #[test]
+        fn only_matches_ids_by_prefix() {
+            let mut engine = create_engine(vec![create_task("a2bc"), create_task("b2cd")]);
+
+            let EngineOutput { mutated_tasks, .. } = engine.run(done("2")).unwrap();
+            assert!(mutated_tasks.is_empty());
+
+            let EngineOutput { mutated_tasks, .. } = engine.run(done("a")).unwrap();
+            assert_eq!(mutated_tasks.len(), 1);
+        }
+
+        #[test]
+        fn errors_when_a_prefix_matches_more_than_one_task() {
+            let mut engine = create_engine(vec![create_task("abcd"), create_task("abef")]);
+
+            let error = engine.run(done("ab")).unwrap_err();
+            assert!(error
+                .to_string()
+                .ends_with("`ab` is ambiguous, it could be any of: abcd abef"));
+        }
+
+        #[test]
+        fn shows_the_shortest_unique_prefix() {
+            let mut engine = create_engine(vec![
+                create_task("abcd"),
+                create_task("abef"),
+                create_task("bcde"),
+            ]);
+
+            let EngineOutput { short_ids, .. } = engine.run(Command::Read(vec![])).unwrap();
+
+            let short_id = |id: &str| short_ids[&Rc::new(Id(id.to_owned()))].as_str();
+            assert_eq!(short_id("abcd"), "abc");
+            assert_eq!(short_id("abef"), "abe");
+            assert_eq!(short_id("bcde"), "b");
+        }
+
+        #[test]
+        fn only_matches_the_exact_id_when_it_is_also_a_prefix_of_another_id() {
+            let mut engine = create_engine(vec![create_task("abcd"), create_task("abcdef")]);
+
+            let EngineOutput { mutated_tasks, .. } = engine.run(done("abcd")).unwrap();
+            assert_eq!(mutated_tasks.len(), 1);
+            assert_eq!(*mutated_tasks[0].get_id(), Id("abcd".to_owned()));
+
+            let EngineOutput { display_tasks, .. } = engine
+                .run(Command::Read(vec![Query::Subtree(Id("abcd".to_owned()))]))
+                .unwrap();
+            assert_eq!(display_tasks.len(), 1);
+        }
+    }
+
+    mod create {
+        use super::*;
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        fn create(id: Option<&str>, description: &str) -> Command {
+            Command::Create(
+                id.map(|id| Id(id.to_owned())),
+                vec![Mutation::SetProp(Prop::Description(description.to_owned()))],
+            )
+        }
+
+        #[test]
+        fn generates_ids_from_the_given_rng() {
+            let created_id = || {
+                let mut engine = create_engine(vec![]).with_rng(StdRng::seed_from_u64(7));
+                let EngineOutput { mutated_tasks, .. } =
+                    engine.run(create(None, "write report")).unwrap();
+
+                mutated_tasks[0].get_id()
+            };
+
+            assert_eq!(created_id(), created_id());
+        }
+
+        #[test]
+        fn updates_the_task_when_the_id_already_exists() {
+            let mut engine = create_engine(vec![create_task("aaaa")]);
+
+            let EngineOutput { mutated_tasks, .. } = engine
+                .run_batch(vec![
+                    create(Some("aaaa"), "write report"),
+                    create(Some("bbbb"), "read report"),
+                    create(Some("bbbb"), "read report again"),
+                ])
+                .unwrap();
+
+            assert_eq!(mutated_tasks.len(), 2);
+
+            let description = |id: &str| {
+                mutated_tasks
+                    .iter()
+                    .find(|task| *task.get_id() == Id(id.to_owned()))
+                    .and_then(|task| task.get_description().clone())
+            };
+            assert_eq!(description("aaaa"), Some("write report".to_owned()));
+            assert_eq!(description("bbbb"), Some("read report again".to_owned()));
+        }
+
+        #[test]
+        fn rejects_ids_that_are_not_safe_file_names() {
+            let mut engine = create_engine(vec![]);
+
+            assert!(engine.run(create(Some("../aaaa"), "write report")).is_err());
+            assert!(engine.run(create(Some(""), "write report")).is_err());
+        }
+    }
+}
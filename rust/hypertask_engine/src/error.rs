@@ -6,6 +6,7 @@ pub enum HyperTaskErrorDomain {
     Render,
     Config,
     Input,
+    Journal,
     Mutation,
     Query,
     ScoreCalculator,
@@ -23,6 +24,7 @@ impl fmt::Display for HyperTaskErrorDomain {
                 HyperTaskErrorDomain::Syncing => "syncing",
                 HyperTaskErrorDomain::Render => "render",
                 HyperTaskErrorDomain::Input => "input",
+                HyperTaskErrorDomain::Journal => "journal",
                 HyperTaskErrorDomain::Mutation => "mutation",
                 HyperTaskErrorDomain::Query => "query",
                 HyperTaskErrorDomain::ScoreCalculator => "scoreCalculator",
@@ -1,8 +1,14 @@
+use crate::error::*;
+use blake2::digest::{Input, VariableOutput};
+use blake2::VarBlake2b;
+use chrono::prelude::*;
 use rand::seq::IteratorRandom;
-use rand::thread_rng;
+use rand::{thread_rng, Rng};
 use serde::{Deserialize, Serialize};
 
+use std::collections::HashMap;
 use std::fmt;
+use std::rc::Rc;
 
 pub const VALID_ID_CHARS: &str = "23456789abcdefghkmnpqrstwxyz";
 pub const NUMBER_OF_CHARS_IN_FULL_ID: usize = 16;
@@ -12,12 +18,18 @@ pub struct Id(pub String);
 
 impl Id {
     pub fn generate() -> Self {
+        Self::generate_with(&mut thread_rng())
+    }
+
+    /// Generates an id from the given source of randomness, so that clients without access to
+    /// the os rng, and tests, can seed their own
+    pub fn generate_with<R: Rng + ?Sized>(rng: &mut R) -> Self {
         let mut result = String::new();
 
         for _ in 0..NUMBER_OF_CHARS_IN_FULL_ID {
             let random = VALID_ID_CHARS
                 .chars()
-                .choose(&mut thread_rng())
+                .choose(rng)
                 .expect("Couldn't get random char");
 
             result.push(random);
@@ -26,13 +38,95 @@ impl Id {
         Id(result)
     }
 
-    pub fn sub_eq(&self, other: &Id) -> bool {
+    /// Derives the id of the next occurrence of a recurring task from its template and the date
+    /// of the occurrence it follows, so that every device that creates it gives it the same id
+    pub fn for_occurrence(template: &Id, occurrence: &DateTime<Utc>) -> Self {
+        let mut hasher =
+            VarBlake2b::new(NUMBER_OF_CHARS_IN_FULL_ID).expect("Couldn't create hasher");
+
+        hasher.input(format!("{}@{}", template, occurrence.to_rfc3339()));
+
+        let valid_chars: Vec<char> = VALID_ID_CHARS.chars().collect();
+
+        Id(hasher
+            .vec_result()
+            .iter()
+            .map(|byte| valid_chars[*byte as usize % valid_chars.len()])
+            .collect())
+    }
+
+    /// Checks an id supplied from outside the engine, ids are used as file names, so they can
+    /// only be made of letters, numbers, `-` and `_`
+    pub fn validate(&self) -> HyperTaskResult<()> {
+        let Id(content) = self;
+
+        if !content.is_empty()
+            && content
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            Ok(())
+        } else {
+            Err(
+                HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                    .with_msg(|| format!("`{}` is not a valid id", content)),
+            )
+        }
+    }
+
+    /// Ids can be referred to by any prefix of them, `2a` refers to `2a7k...`
+    pub fn is_prefix_of(&self, other: &Id) -> bool {
         let Id(self_content) = self;
         let Id(other_content) = other;
 
-        self_content.contains(other_content.as_str())
-            || other_content.contains(self_content.as_str())
+        other_content.starts_with(self_content.as_str())
     }
+
+    /// The start of the id, `length` characters long
+    pub fn prefix(&self, length: usize) -> &str {
+        let Id(content) = self;
+
+        match content.char_indices().nth(length) {
+            Some((index, _)) => &content[..index],
+            None => content,
+        }
+    }
+}
+
+/// Finds how many characters of each id are needed to tell it apart from all of the others
+pub fn get_unique_prefix_lengths<'a>(
+    ids: impl Iterator<Item = &'a Rc<Id>>,
+) -> HashMap<Rc<Id>, usize> {
+    let mut sorted_ids: Vec<&Rc<Id>> = ids.collect();
+    sorted_ids.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let common_prefix_length = |a: &Id, b: &Id| {
+        a.0.chars()
+            .zip(b.0.chars())
+            .take_while(|(a, b)| a == b)
+            .count()
+    };
+
+    // in sorted order, the ids with the longest common prefixes are neighbours
+    let mut lengths = HashMap::new();
+
+    for (index, id) in sorted_ids.iter().enumerate() {
+        let previous = index
+            .checked_sub(1)
+            .map(|previous| common_prefix_length(id, sorted_ids[previous]))
+            .unwrap_or(0);
+        let next = sorted_ids
+            .get(index + 1)
+            .map(|next| common_prefix_length(id, next))
+            .unwrap_or(0);
+
+        lengths.insert(
+            (*id).clone(),
+            (previous.max(next) + 1).min(id.0.chars().count()),
+        );
+    }
+
+    lengths
 }
 
 impl fmt::Display for Id {
@@ -0,0 +1,63 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use time::Duration;
+
+/// A period of time spent working on a task, `stopped_at` is `None` while the task is active
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct WorkInterval {
+    pub started_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub stopped_at: Option<DateTime<Utc>>,
+}
+
+impl WorkInterval {
+    pub fn is_active(&self) -> bool {
+        self.stopped_at.is_none()
+    }
+
+    /// The time spent, active intervals are counted up to `now`
+    pub fn duration(&self, now: &DateTime<Utc>) -> Duration {
+        self.stopped_at.unwrap_or(*now) - self.started_at
+    }
+
+    /// The time spent within `from` and `to`, active intervals are counted up to `now`
+    pub fn duration_between(
+        &self,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+        now: &DateTime<Utc>,
+    ) -> Duration {
+        let start = self.started_at.max(*from);
+        let end = self.stopped_at.unwrap_or(*now).min(*to);
+
+        if end > start {
+            end - start
+        } else {
+            Duration::zero()
+        }
+    }
+}
+
+/// Sorts intervals and closes any that were left active before a later one was started, which
+/// happens when a task is started on two devices
+pub fn normalize_intervals(intervals: &mut Vec<WorkInterval>) {
+    intervals.sort();
+    intervals.dedup_by(|later, earlier| {
+        // the same interval, but only stopped on one device
+        if later.started_at == earlier.started_at {
+            earlier.stopped_at = earlier.stopped_at.or(later.stopped_at);
+            true
+        } else {
+            false
+        }
+    });
+
+    for i in 1..intervals.len() {
+        let next_started_at = intervals[i].started_at;
+
+        if intervals[i - 1].is_active() {
+            intervals[i - 1].stopped_at = Some(next_started_at);
+        }
+    }
+}
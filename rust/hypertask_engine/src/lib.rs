@@ -5,25 +5,38 @@ extern crate serde_derive;
 
 extern crate chrono;
 extern crate derive_builder;
+extern crate regex;
 extern crate serde;
 extern crate time;
 
+mod annotation;
+mod attribute;
+mod dependencies;
 mod engine;
 mod error;
 mod id;
+mod interval;
 mod prop;
+mod query;
 mod recur;
 mod rpn;
+mod status;
 mod tag;
 mod task;
 
 pub mod prelude {
+    pub use crate::annotation::Annotation;
+    pub use crate::attribute::{AttributeSchema, AttributeType, AttributeValue};
+    pub use crate::dependencies::{DependencyGraph, TransitiveMetrics};
     pub use crate::engine::*;
     pub use crate::error::*;
     pub use crate::id::*;
+    pub use crate::interval::WorkInterval;
     pub use crate::prop::Prop;
-    pub use crate::recur::Recur;
+    pub use crate::query::{DescriptionRegex, Query, QueryAttribute};
+    pub use crate::recur::{Frequency, MonthDay, Recur, RecurAnchor, MAX_RECUR_INTERVAL};
     pub use crate::rpn::*;
+    pub use crate::status::Status;
     pub use crate::tag::{Sign, Tag};
-    pub use crate::task::{Score, Task};
+    pub use crate::task::{FieldChange, Score, Task, TaskDiff};
 }
@@ -1,14 +1,26 @@
+use crate::attribute::AttributeValue;
 use crate::id::Id;
 use crate::recur::Recur;
+use crate::tag::Sign;
 use chrono::prelude::*;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub enum Prop {
     Description(String),
-    Blocked(Option<Id>),
+    /// replaces every blocker of the task
+    Blocked(Vec<Id>),
+    /// adds or removes a single blocker
+    Blocker(Sign, Id),
     Done(DateTime<Utc>),
     Due(Option<DateTime<Utc>>),
+    Parent(Option<Id>),
     Recur(Option<Recur>),
+    /// ends the recurrence of a recurring task after a date
+    RecurUntil(Option<DateTime<Utc>>),
+    /// limits the number of occurrences left of a recurring task
+    RecurCount(Option<u32>),
     Snooze(Option<DateTime<Utc>>),
     Wait(Option<DateTime<Utc>>),
+    /// sets or clears a user defined attribute
+    Attribute(String, Option<AttributeValue>),
 }
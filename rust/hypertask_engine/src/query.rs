@@ -0,0 +1,117 @@
+use crate::attribute::AttributeValue;
+use crate::error::*;
+use crate::id::Id;
+use crate::status::Status;
+use crate::tag::Tag;
+use chrono::prelude::*;
+use regex::Regex;
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+
+/// A regular expression that can be matched against a task's description
+#[derive(Debug, Clone)]
+pub struct DescriptionRegex(Regex);
+
+impl DescriptionRegex {
+    pub fn new(pattern: &str) -> HyperTaskResult<Self> {
+        Regex::new(pattern).map(DescriptionRegex).map_err(|e| {
+            HyperTaskError::new(HyperTaskErrorDomain::Query, HyperTaskErrorAction::Parse)
+                .with_msg(|| format!("`{}` is not a valid regular expression", pattern))
+                .from(e)
+        })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.0.is_match(text)
+    }
+}
+
+impl Serialize for DescriptionRegex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for DescriptionRegex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+
+        Regex::new(&pattern)
+            .map(DescriptionRegex)
+            .map_err(de::Error::custom)
+    }
+}
+
+/// The attributes of a task that can be queried on directly
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum QueryAttribute {
+    CreatedAt,
+    UpdatedAt,
+    Description,
+    Blocked,
+    Done,
+    Due,
+    Parent,
+    Recur,
+    Snooze,
+    Wait,
+}
+
+impl QueryAttribute {
+    pub fn is_date(self) -> bool {
+        match self {
+            QueryAttribute::CreatedAt
+            | QueryAttribute::UpdatedAt
+            | QueryAttribute::Done
+            | QueryAttribute::Due
+            | QueryAttribute::Snooze
+            | QueryAttribute::Wait => true,
+            QueryAttribute::Description
+            | QueryAttribute::Blocked
+            | QueryAttribute::Parent
+            | QueryAttribute::Recur => false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum Query {
+    Id(Id),
+    /// matches only the task with exactly this id, the engine resolves ids that belong to a task
+    /// to this, so that they don't also match the longer ids that they're a prefix of
+    ExactId(Id),
+    Tag(Tag),
+    /// matches tasks whose description contains the text
+    Description(String),
+    /// matches tasks whose description contains the word, ignoring case
+    Word(String),
+    Regex(DescriptionRegex),
+    /// matches tasks where the attribute is set
+    Has(QueryAttribute),
+    /// matches tasks where the date attribute is set, and is before the date
+    Before(QueryAttribute, DateTime<Utc>),
+    /// matches tasks where the date attribute is set, and is after the date
+    After(QueryAttribute, DateTime<Utc>),
+    BlockedBy(Id),
+    /// matches the direct children of the task
+    Parent(Id),
+    /// matches the task and everything beneath it, the engine expands this to the ids of all of
+    /// the task's descendants before running the query
+    Subtree(Id),
+    /// matches every instance of a recurring task, done or not
+    Template(Id),
+    /// matches tasks with the status, the engine expands this to the ids of the matching tasks
+    /// before running the query
+    Status(Status),
+    /// matches tasks where the user defined attribute is set
+    HasAttribute(String),
+    /// matches tasks where the user defined attribute is set to the value
+    AttributeEq(String, AttributeValue),
+    /// matches tasks where the user defined attribute is set, and is less than the value
+    AttributeBelow(String, AttributeValue),
+    /// matches tasks where the user defined attribute is set, and is greater than the value
+    AttributeAbove(String, AttributeValue),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+}
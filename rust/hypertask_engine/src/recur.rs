@@ -1,33 +1,834 @@
+use crate::error::*;
+use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 use std::fmt;
 use time::Duration;
 
+/// The largest interval a recurrence can have, larger intervals would put the next occurrence
+/// beyond the dates that can be represented
+pub const MAX_RECUR_INTERVAL: i64 = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Frequency {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+/// Which day of the month a monthly recurrence falls on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum MonthDay {
+    /// the nth weekday of the month, counting back from the end of the month if negative
+    Weekday(i32, Weekday),
+    LastDay,
+}
+
+/// What the next occurrence of a recurring task is scheduled from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum RecurAnchor {
+    /// the previous due date, so occurrences stay on a fixed schedule
+    Due,
+    /// the time the task was done, so a task done late isn't immediately due again
+    Done,
+}
+
+impl RecurAnchor {
+    fn due() -> Self {
+        RecurAnchor::Due
+    }
+
+    fn is_due(&self) -> bool {
+        *self == RecurAnchor::Due
+    }
+}
+
+/// A recurrence rule, modelled on the iCalendar RRULE
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
-pub enum Recur {
-    Day(i64),
-    Week(i64),
-    Month(i64),
-    Year(i64),
+#[serde(from = "StoredRecur")]
+pub struct Recur {
+    pub frequency: Frequency,
+    pub interval: i64,
+    #[serde(skip_serializing_if = "RecurAnchor::is_due")]
+    #[serde(default = "RecurAnchor::due")]
+    pub anchor: RecurAnchor,
+    /// the days of the week that daily and weekly recurrences fall on, any day if empty
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub weekdays: Vec<Weekday>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub month_day: Option<MonthDay>,
+    /// the day of the month that monthly and yearly recurrences are scheduled on, so that the
+    /// 31st still recurs on the 31st after a shorter month. Set from the first occurrence
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub day_of_month: Option<u32>,
+    /// the task stops recurring after this date
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    /// the number of occurrences left, including the current one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub count: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct RecurRule {
+    frequency: Frequency,
+    interval: i64,
+    #[serde(default = "RecurAnchor::due")]
+    anchor: RecurAnchor,
+    #[serde(default)]
+    weekdays: Vec<Weekday>,
+    #[serde(default)]
+    month_day: Option<MonthDay>,
+    #[serde(default)]
+    day_of_month: Option<u32>,
+    #[serde(default)]
+    until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    count: Option<u32>,
+}
+
+/// Recurrences used to only be a number of days, weeks, months or years, eg: `{ "Day": 3 }`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredRecur {
+    LegacyMap(std::collections::HashMap<Frequency, i64>),
+    Rule(RecurRule),
+}
+
+impl From<StoredRecur> for Recur {
+    fn from(stored: StoredRecur) -> Self {
+        match stored {
+            StoredRecur::LegacyMap(map) => map
+                .into_iter()
+                .next()
+                .map(|(frequency, interval)| Recur::every(frequency, interval))
+                .unwrap_or_else(|| Recur::every(Frequency::Day, 1)),
+            StoredRecur::Rule(rule) => Recur {
+                frequency: rule.frequency,
+                interval: rule.interval,
+                anchor: rule.anchor,
+                weekdays: rule.weekdays,
+                month_day: rule.month_day,
+                day_of_month: rule.day_of_month,
+                until: rule.until,
+                count: rule.count,
+            },
+        }
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    (28..=31)
+        .rev()
+        .find(|day| NaiveDate::from_ymd_opt(year, month, *day).is_some())
+        .unwrap_or(28)
+}
+
+/// Adds months to a date, landing on `day` of the month, which is clamped to the end of the
+/// month if the month is too short, `None` if the date can't be represented
+fn add_months(date: NaiveDate, months: i64, day: u32) -> Option<NaiveDate> {
+    let total_months =
+        (i64::from(date.year()) * 12 + i64::from(date.month0())).checked_add(months)?;
+    let year = i32::try_from(total_months.div_euclid(12)).ok()?;
+    let month = total_months.rem_euclid(12) as u32 + 1;
+
+    NaiveDate::from_ymd_opt(year, month, day.min(days_in_month(year, month)))
+}
+
+/// Finds the day that `month_day` refers to in the month, if the month has that day
+fn resolve_month_day(year: i32, month: u32, month_day: MonthDay) -> Option<NaiveDate> {
+    let last_day = days_in_month(year, month);
+
+    match month_day {
+        MonthDay::LastDay => NaiveDate::from_ymd_opt(year, month, last_day),
+        MonthDay::Weekday(0, _) => None,
+        MonthDay::Weekday(n, weekday) if n > 0 => {
+            let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+            let offset =
+                (weekday.num_days_from_monday() + 7 - first.weekday().num_days_from_monday()) % 7;
+            let day = 1 + offset + (n as u32 - 1) * 7;
+
+            if day <= last_day {
+                NaiveDate::from_ymd_opt(year, month, day)
+            } else {
+                None
+            }
+        }
+        MonthDay::Weekday(n, weekday) => {
+            let last = NaiveDate::from_ymd_opt(year, month, last_day)?;
+            let offset =
+                (last.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+            let back = offset + (n.unsigned_abs() - 1) * 7;
+
+            if back < last_day {
+                NaiveDate::from_ymd_opt(year, month, last_day - back)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Converts a local time back to utc, times that are skipped when the clocks go forward are
+/// moved forward by an hour
+fn local_to_utc<Tz: TimeZone>(time_zone: &Tz, local: &NaiveDateTime) -> DateTime<Utc> {
+    time_zone
+        .from_local_datetime(local)
+        .earliest()
+        .or_else(|| {
+            time_zone
+                .from_local_datetime(&(*local + Duration::hours(1)))
+                .earliest()
+        })
+        .map(|date_time| date_time.with_timezone(&Utc))
+        .unwrap_or_else(|| DateTime::from_utc(*local, Utc))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn next_in_local_time(recur: &Recur, date_time: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+    recur.next_in(date_time, &Local)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn after_occurrence_in_local_time(recur: &Recur, occurrence: &DateTime<Utc>) -> Recur {
+    recur.after_occurrence_in(occurrence, &Local)
+}
+
+// there's no reliable way to get the local time zone from wasm
+#[cfg(target_arch = "wasm32")]
+fn next_in_local_time(recur: &Recur, date_time: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+    recur.next_in(date_time, &Utc)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn after_occurrence_in_local_time(recur: &Recur, occurrence: &DateTime<Utc>) -> Recur {
+    recur.after_occurrence_in(occurrence, &Utc)
+}
+
+fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "mon",
+        Weekday::Tue => "tue",
+        Weekday::Wed => "wed",
+        Weekday::Thu => "thu",
+        Weekday::Fri => "fri",
+        Weekday::Sat => "sat",
+        Weekday::Sun => "sun",
+    }
 }
 
-impl From<&Recur> for Duration {
-    fn from(recur: &Recur) -> Self {
-        match recur {
-            Recur::Day(n) => Duration::days(*n),
-            Recur::Week(n) => Duration::weeks(*n),
-            Recur::Month(n) => Duration::seconds(n * 60 * 60 * 24 * 365 / 12),
-            Recur::Year(n) => Duration::days(n * 365),
+fn ordinal(n: i32) -> String {
+    match n {
+        -1 => "last".to_string(),
+        n if n < 0 => format!("{} from last", ordinal(-n)),
+        n if n % 10 == 1 && n % 100 != 11 => format!("{}st", n),
+        n if n % 10 == 2 && n % 100 != 12 => format!("{}nd", n),
+        n if n % 10 == 3 && n % 100 != 13 => format!("{}rd", n),
+        n => format!("{}th", n),
+    }
+}
+
+fn rrule_parse_error(rrule: &str) -> HyperTaskError {
+    HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+        .with_msg(|| format!("`{}` is not a supported RRULE", rrule))
+}
+
+fn parse_rrule_weekday(s: &str) -> Option<Weekday> {
+    match s {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+impl Recur {
+    /// Recurs every `interval` days, weeks, months or years
+    pub fn every(frequency: Frequency, interval: i64) -> Self {
+        Recur {
+            frequency,
+            interval,
+            anchor: RecurAnchor::Due,
+            weekdays: vec![],
+            month_day: None,
+            day_of_month: None,
+            until: None,
+            count: None,
+        }
+    }
+
+    /// Finds the next date the recurrence lands on after `date`, `None` if the interval is out of
+    /// range or the next date can't be represented
+    fn next_date(&self, date: NaiveDate) -> Option<NaiveDate> {
+        if self.interval < 1 || self.interval > MAX_RECUR_INTERVAL {
+            return None;
+        }
+
+        let day = self.day_of_month.unwrap_or_else(|| date.day());
+
+        match (self.frequency, self.month_day) {
+            (Frequency::Day, _) => {
+                let mut next = date.checked_add_signed(Duration::days(self.interval))?;
+
+                // bounded, as some intervals can never land on the right weekday
+                for _ in 0..7 {
+                    if self.weekdays.is_empty() || self.weekdays.contains(&next.weekday()) {
+                        break;
+                    }
+                    next = next.checked_add_signed(Duration::days(self.interval))?;
+                }
+
+                Some(next)
+            }
+
+            (Frequency::Week, _) if !self.weekdays.is_empty() => {
+                let mut days: Vec<u32> = self
+                    .weekdays
+                    .iter()
+                    .map(|weekday| weekday.num_days_from_monday())
+                    .collect();
+                days.sort();
+
+                let today = date.weekday().num_days_from_monday();
+
+                match days.iter().find(|day| **day > today) {
+                    Some(day) => date.checked_add_signed(Duration::days(i64::from(day - today))),
+                    None => date.checked_add_signed(
+                        Duration::weeks(self.interval)
+                            + Duration::days(i64::from(days[0]) - i64::from(today)),
+                    ),
+                }
+            }
+            (Frequency::Week, _) => date.checked_add_signed(Duration::weeks(self.interval)),
+
+            (Frequency::Month, Some(month_day)) => {
+                let first_of_month = date.with_day(1)?;
+
+                (0..=12)
+                    .filter_map(|i| {
+                        let month = add_months(first_of_month, i * self.interval, 1)?;
+                        resolve_month_day(month.year(), month.month(), month_day)
+                    })
+                    .find(|next| *next > date)
+                    .or_else(|| add_months(date, self.interval, date.day()))
+            }
+            (Frequency::Month, None) => add_months(date, self.interval, day),
+
+            (Frequency::Year, _) => add_months(date, self.interval * 12, day),
+        }
+    }
+
+    /// Finds the next occurrence after `date_time`, using the calendar of the local time zone,
+    /// `None` if the recurrence has ended
+    pub fn next(&self, date_time: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        next_in_local_time(self, date_time)
+    }
+
+    /// Finds the next occurrence after `date_time`, using the calendar of `time_zone`. The time
+    /// of day is kept the same across daylight saving changes, and months and years are
+    /// calendar months and years, so the 31st of January recurs on the last day of February
+    pub fn next_in<Tz: TimeZone>(
+        &self,
+        date_time: &DateTime<Utc>,
+        time_zone: &Tz,
+    ) -> Option<DateTime<Utc>> {
+        if self.count.map(|count| count <= 1).unwrap_or(false) {
+            return None;
+        }
+
+        let local = date_time.with_timezone(time_zone).naive_local();
+        let next = local_to_utc(
+            time_zone,
+            &self.next_date(local.date())?.and_time(local.time()),
+        );
+
+        match self.until {
+            Some(until) if next > until => None,
+            _ => Some(next),
+        }
+    }
+
+    /// The recurrence that is left once `occurrence` has been done, using the calendar of the
+    /// local time zone
+    pub fn after_occurrence(&self, occurrence: &DateTime<Utc>) -> Self {
+        after_occurrence_in_local_time(self, occurrence)
+    }
+
+    /// The recurrence that is left once `occurrence` has been done, using the calendar of
+    /// `time_zone`. Monthly and yearly recurrences remember the day of the month of the first
+    /// occurrence, as the dates they're moved to may have been clamped to a shorter month
+    pub fn after_occurrence_in<Tz: TimeZone>(
+        &self,
+        occurrence: &DateTime<Utc>,
+        time_zone: &Tz,
+    ) -> Self {
+        let is_on_day_of_month = match self.frequency {
+            Frequency::Month => self.month_day.is_none(),
+            Frequency::Year => true,
+            _ => false,
+        };
+
+        let day_of_month = if is_on_day_of_month && self.anchor == RecurAnchor::Due {
+            self.day_of_month
+                .or_else(|| Some(occurrence.with_timezone(time_zone).day()))
+        } else {
+            self.day_of_month
+        };
+
+        Recur {
+            count: self.count.map(|count| count.saturating_sub(1)),
+            day_of_month,
+            ..self.clone()
+        }
+    }
+
+    /// Formats the recurrence as an iCalendar RRULE, eg: `FREQ=WEEKLY;INTERVAL=1;BYDAY=MO,FR`.
+    /// RRULEs can't be anchored to when a task was done, so the anchor isn't included
+    pub fn to_rrule(&self) -> String {
+        let mut parts = vec![
+            format!(
+                "FREQ={}",
+                match self.frequency {
+                    Frequency::Day => "DAILY",
+                    Frequency::Week => "WEEKLY",
+                    Frequency::Month => "MONTHLY",
+                    Frequency::Year => "YEARLY",
+                }
+            ),
+            format!("INTERVAL={}", self.interval),
+        ];
+
+        // an rrule can only have one BYDAY, the nth weekday of the month is used over the days of
+        // the week, as monthly recurrences don't use the days of the week
+        match self.month_day {
+            Some(MonthDay::Weekday(n, weekday)) => parts.push(format!(
+                "BYDAY={}{}",
+                n,
+                weekday_name(weekday)[..2].to_uppercase()
+            )),
+            Some(MonthDay::LastDay) => parts.push("BYMONTHDAY=-1".to_string()),
+            None => {}
+        }
+
+        if !self.weekdays.is_empty() && !matches!(self.month_day, Some(MonthDay::Weekday(..))) {
+            parts.push(format!(
+                "BYDAY={}",
+                self.weekdays
+                    .iter()
+                    .map(|weekday| weekday_name(*weekday)[..2].to_uppercase())
+                    .collect::<Vec<String>>()
+                    .join(",")
+            ));
+        }
+
+        if let Some(until) = self.until {
+            parts.push(format!("UNTIL={}", until.format("%Y%m%dT%H%M%SZ")));
+        }
+
+        if let Some(count) = self.count {
+            parts.push(format!("COUNT={}", count));
+        }
+
+        parts.join(";")
+    }
+
+    /// Parses an iCalendar RRULE, only the parts of the standard that hypertask can represent
+    /// are supported
+    pub fn from_rrule(rrule: &str) -> HyperTaskResult<Self> {
+        let rrule_body = rrule.trim_start_matches("RRULE:");
+        let mut recur = Recur::every(Frequency::Day, 1);
+        let mut has_frequency = false;
+
+        for part in rrule_body.split(';').filter(|part| !part.is_empty()) {
+            let equals_index = part.find('=').ok_or_else(|| rrule_parse_error(rrule))?;
+            let (key, value) = (&part[..equals_index], &part[equals_index + 1..]);
+
+            match key {
+                "FREQ" => {
+                    has_frequency = true;
+                    recur.frequency = match value {
+                        "DAILY" => Frequency::Day,
+                        "WEEKLY" => Frequency::Week,
+                        "MONTHLY" => Frequency::Month,
+                        "YEARLY" => Frequency::Year,
+                        _ => return Err(rrule_parse_error(rrule)),
+                    };
+                }
+                "INTERVAL" => {
+                    recur.interval = match value.parse() {
+                        Ok(interval) if interval > 0 && interval <= MAX_RECUR_INTERVAL => interval,
+                        _ => return Err(rrule_parse_error(rrule)),
+                    };
+                }
+                "BYDAY" => {
+                    let mut nth_weekdays = 0;
+
+                    for day in value.split(',') {
+                        // the weekday is the last two characters, which may follow a number
+                        let (n, weekday) = day
+                            .char_indices()
+                            .rev()
+                            .nth(1)
+                            .and_then(|(index, _)| {
+                                parse_rrule_weekday(&day[index..])
+                                    .map(|weekday| (&day[..index], weekday))
+                            })
+                            .ok_or_else(|| rrule_parse_error(rrule))?;
+
+                        match n {
+                            "" => recur.weekdays.push(weekday),
+                            n => {
+                                // a month has at most 5 of each weekday, and there is no 0th
+                                let n = match n.parse::<i32>() {
+                                    Ok(n) if n != 0 && n.abs() <= 5 => n,
+                                    _ => return Err(rrule_parse_error(rrule)),
+                                };
+                                recur.month_day = Some(MonthDay::Weekday(n, weekday));
+                                nth_weekdays += 1;
+                            }
+                        }
+                    }
+
+                    // only one day of the month, and not alongside days of the week, can be
+                    // represented
+                    if nth_weekdays > 1 || (nth_weekdays == 1 && !recur.weekdays.is_empty()) {
+                        return Err(rrule_parse_error(rrule));
+                    }
+                }
+                "BYMONTHDAY" if value == "-1" => recur.month_day = Some(MonthDay::LastDay),
+                "UNTIL" => {
+                    let until = match value.len() {
+                        8 => NaiveDate::parse_from_str(value, "%Y%m%d").map(|d| d.and_hms(0, 0, 0)),
+                        _ => NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ"),
+                    }
+                    .map_err(|_| rrule_parse_error(rrule))?;
+
+                    recur.until = Some(DateTime::from_utc(until, Utc));
+                }
+                "COUNT" => {
+                    recur.count = Some(value.parse().map_err(|_| rrule_parse_error(rrule))?);
+                }
+                _ => return Err(rrule_parse_error(rrule)),
+            }
+        }
+
+        // days of the week are only scheduled daily or weekly, and a day of the month only monthly
+        let is_supported = match recur.frequency {
+            Frequency::Day | Frequency::Week => recur.month_day.is_none(),
+            Frequency::Month => recur.weekdays.is_empty(),
+            Frequency::Year => recur.weekdays.is_empty() && recur.month_day.is_none(),
+        };
+
+        if has_frequency && is_supported {
+            Ok(recur)
+        } else {
+            Err(rrule_parse_error(rrule))
         }
     }
 }
 
 impl fmt::Display for Recur {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Recur::Day(n) => write!(f, "{} days", n),
-            Recur::Week(n) => write!(f, "{} weeks", n),
-            Recur::Month(n) => write!(f, "{} months", n),
-            Recur::Year(n) => write!(f, "{} years", n),
+        match self.frequency {
+            Frequency::Day => write!(f, "{} days", self.interval)?,
+            Frequency::Week => write!(f, "{} weeks", self.interval)?,
+            Frequency::Month => write!(f, "{} months", self.interval)?,
+            Frequency::Year => write!(f, "{} years", self.interval)?,
+        }
+
+        if self.anchor == RecurAnchor::Done {
+            write!(f, " after done")?;
+        }
+
+        if !self.weekdays.is_empty() {
+            write!(
+                f,
+                " on {}",
+                self.weekdays
+                    .iter()
+                    .map(|weekday| weekday_name(*weekday))
+                    .collect::<Vec<&str>>()
+                    .join(",")
+            )?;
+        }
+
+        match self.month_day {
+            Some(MonthDay::Weekday(n, weekday)) => {
+                write!(f, " on the {} {}", ordinal(n), weekday_name(weekday))?
+            }
+            Some(MonthDay::LastDay) => write!(f, " on the last day")?,
+            None => {}
+        }
+
+        if let Some(until) = self.until {
+            write!(f, " until {}", until.format("%Y-%m-%d"))?;
+        }
+
+        if let Some(count) = self.count {
+            write!(f, ", {} left", count)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn next_date(recur: &Recur, date: NaiveDate) -> NaiveDate {
+        recur
+            .next_in(&DateTime::from_utc(date.and_hms(9, 0, 0), Utc), &Utc)
+            .unwrap()
+            .naive_utc()
+            .date()
+    }
+
+    #[test]
+    fn months_follow_the_calendar() {
+        let due = Utc.ymd(2019, 1, 1).and_hms(9, 0, 0);
+        let recur = Recur::every(Frequency::Month, 1);
+
+        let mut next = due;
+        for _ in 0..12 {
+            next = recur.next_in(&next, &Utc).unwrap();
+            assert_eq!(next.day(), 1);
+        }
+
+        assert_eq!(next, Utc.ymd(2020, 1, 1).and_hms(9, 0, 0));
+    }
+
+    #[test]
+    fn clamps_to_the_end_of_short_months() {
+        let due = Utc.ymd(2020, 1, 31).and_hms(9, 0, 0);
+
+        assert_eq!(
+            Recur::every(Frequency::Month, 1).next_in(&due, &Utc),
+            Some(Utc.ymd(2020, 2, 29).and_hms(9, 0, 0))
+        );
+        assert_eq!(
+            Recur::every(Frequency::Year, 1).next_in(&Utc.ymd(2020, 2, 29).and_hms(9, 0, 0), &Utc),
+            Some(Utc.ymd(2021, 2, 28).and_hms(9, 0, 0))
+        );
+    }
+
+    /// The dates of the next occurrences, completing each one as it comes around
+    fn chain(recur: &Recur, due: DateTime<Utc>, count: usize) -> Vec<NaiveDate> {
+        let mut recur = recur.clone();
+        let mut due = due;
+        let mut dates = vec![];
+
+        for _ in 0..count {
+            let next = recur.next_in(&due, &Utc).unwrap();
+            recur = recur.after_occurrence_in(&due, &Utc);
+            due = next;
+            dates.push(due.naive_utc().date());
+        }
+
+        dates
+    }
+
+    #[test]
+    fn clamped_dates_return_to_the_original_day() {
+        assert_eq!(
+            chain(
+                &Recur::every(Frequency::Month, 1),
+                Utc.ymd(2024, 1, 31).and_hms(9, 0, 0),
+                2
+            ),
+            vec![
+                NaiveDate::from_ymd(2024, 2, 29),
+                NaiveDate::from_ymd(2024, 3, 31)
+            ]
+        );
+        assert_eq!(
+            chain(
+                &Recur::every(Frequency::Year, 1),
+                Utc.ymd(2024, 2, 29).and_hms(9, 0, 0),
+                4
+            ),
+            vec![
+                NaiveDate::from_ymd(2025, 2, 28),
+                NaiveDate::from_ymd(2026, 2, 28),
+                NaiveDate::from_ymd(2027, 2, 28),
+                NaiveDate::from_ymd(2028, 2, 29)
+            ]
+        );
+    }
+
+    #[test]
+    fn uses_the_local_calendar() {
+        let time_zone = FixedOffset::east(10 * 60 * 60);
+
+        // this is the 1st of March in the time zone, but the 28th of February in utc
+        let due = Utc.ymd(2019, 2, 28).and_hms(15, 0, 0);
+        let recur = Recur::every(Frequency::Month, 1);
+
+        assert_eq!(
+            recur.next_in(&due, &time_zone),
+            Some(
+                time_zone
+                    .ymd(2019, 4, 1)
+                    .and_hms(1, 0, 0)
+                    .with_timezone(&Utc)
+            )
+        );
+        assert_eq!(
+            recur.next_in(&due, &Utc),
+            Some(Utc.ymd(2019, 3, 28).and_hms(15, 0, 0))
+        );
+    }
+
+    #[test]
+    fn recurs_on_weekdays() {
+        let recur = Recur {
+            weekdays: vec![Weekday::Mon, Weekday::Wed, Weekday::Fri],
+            ..Recur::every(Frequency::Week, 1)
+        };
+
+        // a wednesday, then a friday
+        assert_eq!(
+            next_date(&recur, NaiveDate::from_ymd(2019, 12, 2)),
+            NaiveDate::from_ymd(2019, 12, 4)
+        );
+        assert_eq!(
+            next_date(&recur, NaiveDate::from_ymd(2019, 12, 6)),
+            NaiveDate::from_ymd(2019, 12, 9)
+        );
+    }
+
+    #[test]
+    fn recurs_on_nth_weekday_and_last_day_of_month() {
+        let second_tuesday = Recur {
+            month_day: Some(MonthDay::Weekday(2, Weekday::Tue)),
+            ..Recur::every(Frequency::Month, 1)
+        };
+
+        assert_eq!(
+            next_date(&second_tuesday, NaiveDate::from_ymd(2019, 12, 10)),
+            NaiveDate::from_ymd(2020, 1, 14)
+        );
+
+        let last_day = Recur {
+            month_day: Some(MonthDay::LastDay),
+            ..Recur::every(Frequency::Month, 1)
+        };
+
+        assert_eq!(
+            next_date(&last_day, NaiveDate::from_ymd(2020, 1, 31)),
+            NaiveDate::from_ymd(2020, 2, 29)
+        );
+    }
+
+    #[test]
+    fn stops_at_end_conditions() {
+        let due = Utc.ymd(2019, 12, 30).and_hms(9, 0, 0);
+
+        let until = Recur {
+            until: Some(Utc.ymd(2019, 12, 31).and_hms(0, 0, 0)),
+            ..Recur::every(Frequency::Day, 1)
+        };
+        assert_eq!(until.next_in(&due, &Utc), None);
+
+        let count = Recur {
+            count: Some(2),
+            ..Recur::every(Frequency::Day, 1)
+        };
+        assert!(count.next_in(&due, &Utc).is_some());
+        assert_eq!(
+            count.after_occurrence_in(&due, &Utc).next_in(&due, &Utc),
+            None
+        );
+    }
+
+    #[test]
+    fn round_trips_through_rrule() {
+        let recur = Recur {
+            month_day: Some(MonthDay::Weekday(-1, Weekday::Fri)),
+            until: Some(Utc.ymd(2020, 6, 1).and_hms(0, 0, 0)),
+            ..Recur::every(Frequency::Month, 2)
+        };
+
+        assert_eq!(
+            recur.to_rrule(),
+            "FREQ=MONTHLY;INTERVAL=2;BYDAY=-1FR;UNTIL=20200601T000000Z"
+        );
+        assert_eq!(Recur::from_rrule(&recur.to_rrule()), Ok(recur));
+    }
+
+    #[test]
+    fn emits_a_single_byday() {
+        let recur = Recur {
+            weekdays: vec![Weekday::Mon],
+            month_day: Some(MonthDay::Weekday(1, Weekday::Fri)),
+            ..Recur::every(Frequency::Month, 1)
+        };
+
+        assert_eq!(recur.to_rrule(), "FREQ=MONTHLY;INTERVAL=1;BYDAY=1FR");
+    }
+
+    #[test]
+    fn rejects_rrules_that_cant_be_represented() {
+        for rrule in &[
+            "FREQ=WEEKLY;BYDAY=M",
+            "FREQ=WEEKLY;BYDAY=é",
+            "FREQ=WEEKLY;BYDAY=1é",
+            "FREQ=DAILY;INTERVAL=0",
+            "FREQ=DAILY;INTERVAL=-2",
+            "FREQ=MONTHLY;BYDAY=MO,1FR",
+            "FREQ=MONTHLY;BYDAY=1MO,-1FR",
+            "FREQ=MONTHLY;BYDAY=0MO",
+            "FREQ=MONTHLY;BYDAY=+0MO",
+            "FREQ=MONTHLY;BYDAY=6MO",
+            "FREQ=MONTHLY;BYDAY=-6MO",
+            "FREQ=YEARLY;INTERVAL=1000000",
+            "FREQ=MONTHLY;BYDAY=MO",
+            "FREQ=DAILY;BYDAY=2MO",
+            "FREQ=WEEKLY;BYMONTHDAY=-1",
+            "FREQ=YEARLY;BYDAY=1MO",
+            "FREQ=YEARLY;BYDAY=MO",
+            "FREQ=YEARLY;BYMONTHDAY=-1",
+        ] {
+            assert!(Recur::from_rrule(rrule).is_err(), "{}", rrule);
+        }
+    }
+
+    #[test]
+    fn ends_instead_of_overflowing_the_calendar() {
+        let due = Utc.ymd(2019, 1, 1).and_hms(9, 0, 0);
+
+        for frequency in &[
+            Frequency::Day,
+            Frequency::Week,
+            Frequency::Month,
+            Frequency::Year,
+        ] {
+            assert_eq!(
+                Recur::every(*frequency, 1_000_000).next_in(&due, &Utc),
+                None
+            );
+            assert_eq!(
+                Recur::every(*frequency, MAX_RECUR_INTERVAL)
+                    .next_in(&Utc.ymd(262_143, 12, 1).and_hms(9, 0, 0), &Utc),
+                None
+            );
         }
     }
+
+    #[test]
+    fn loads_legacy_recurrences() {
+        let recur: Recur = serde_json::from_str(r#"{ "Week": 2 }"#).unwrap();
+
+        assert_eq!(recur, Recur::every(Frequency::Week, 2));
+    }
 }
@@ -1,5 +1,7 @@
+use crate::attribute::AttributeSchema;
+use crate::dependencies::DependencyGraph;
 use crate::error::*;
-use crate::id::Id;
+use crate::status::Status;
 use crate::task::Task;
 use chrono::prelude::*;
 use std::collections::HashMap;
@@ -102,17 +104,43 @@ pub struct StackMachine {
     stack: Vec<RPNSymbol>,
     instructions: Rc<Vec<RPNSymbol>>,
     environment: HashMap<&'static str, f64>,
+    attribute_schema: AttributeSchema,
 }
 
 impl StackMachine {
-    pub fn new(instructions: Vec<RPNSymbol>, environment: HashMap<&'static str, f64>) -> Self {
+    pub fn new(
+        instructions: Vec<RPNSymbol>,
+        mut environment: HashMap<&'static str, f64>,
+    ) -> Self {
+        for status in Status::ALL.iter() {
+            environment
+                .entry(status.name())
+                .or_insert_with(|| status.to_number());
+        }
+
         Self {
             stack: Vec::with_capacity((instructions.len() as f64).sqrt() as usize),
             instructions: Rc::new(instructions),
             environment,
+            attribute_schema: AttributeSchema::default(),
         }
     }
 
+    /// Lets programs read the user defined attributes in the schema with `:`
+    pub fn with_attribute_schema(mut self, attribute_schema: AttributeSchema) -> Self {
+        self.attribute_schema = attribute_schema;
+        self
+    }
+
+    /// Programs are run against the time in the environment, so that every task is scored
+    /// against the same time
+    fn get_now(&self) -> DateTime<Utc> {
+        self.environment
+            .get("now")
+            .map(|now| Utc.timestamp(*now as i64, 0))
+            .unwrap_or_else(Utc::now)
+    }
+
     fn pop(&mut self) -> HyperTaskResult<RPNSymbol> {
         self.stack.pop().ok_or_else(|| {
             HyperTaskError::new(
@@ -207,7 +235,7 @@ impl StackMachine {
         &mut self,
         task: &Task,
 
-        dependants_map: &HashMap<Rc<Id>, Vec<Rc<Task>>>,
+        dependency_graph: &DependencyGraph,
     ) -> HyperTaskResult<()> {
         let prop_name = self.pop_symbol()?;
 
@@ -219,15 +247,54 @@ impl StackMachine {
             "updated_at" => task.get_updated_at().timestamp() as f64,
             "wait" => sanitize_date_time(task.get_wait()),
 
-            "blocked" => match task.get_blocked_by() {
-                Some(_) => 1.0,
-                None => 0.0,
-            },
+            "blocked" => {
+                if dependency_graph.get_open_blockers(task).next().is_some() {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
 
-            "blocking" => match dependants_map.get(&task.get_id()) {
-                Some(ds) => ds.len() as f64,
-                None => 0.0,
-            },
+            "blocker_count" => dependency_graph.get_open_blockers(task).count() as f64,
+
+            "blocking" => dependency_graph.get_dependants(&task.get_id()).len() as f64,
+
+            "transitive_blocking" => dependency_graph
+                .get_transitive_metrics(&task.get_id())
+                .map_or(0, |metrics| metrics.dependants) as f64,
+
+            "transitive_due" => sanitize_date_time(
+                &dependency_graph
+                    .get_transitive_metrics(&task.get_id())
+                    .and_then(|metrics| metrics.earliest_due),
+            ),
+
+            "chain_depth" => dependency_graph
+                .get_transitive_metrics(&task.get_id())
+                .map_or(0, |metrics| metrics.depth) as f64,
+
+            "children" => dependency_graph.get_open_children(&task.get_id()).count() as f64,
+
+            "progress" => dependency_graph.get_progress(&task.get_id()).unwrap_or(0.0),
+
+            "status" => Status::of(task, dependency_graph, &self.get_now()).to_number(),
+
+            "active" => {
+                if task.is_active() {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+
+            "time_spent" => task.get_time_spent(&self.get_now()).num_seconds() as f64,
+
+            name if self.attribute_schema.get(name).is_some() => {
+                match task.get_attributes().get(name) {
+                    Some(value) => self.attribute_schema.to_number(name, value)?,
+                    None => 0.0,
+                }
+            }
 
             _ => {
                 return Err(HyperTaskError::new(
@@ -302,7 +369,7 @@ impl StackMachine {
     pub fn run_on(
         &mut self,
         task: &Task,
-        dependants_map: &HashMap<Rc<Id>, Vec<Rc<Task>>>,
+        dependency_graph: &DependencyGraph,
     ) -> HyperTaskResult<f64> {
         self.stack.clear();
 
@@ -315,7 +382,7 @@ impl StackMachine {
                 RPNSymbol::Duplicate => self.run_duplicate(),
                 RPNSymbol::Equal => self.run_equal(),
                 RPNSymbol::GetEnvironment => self.run_get_environment(),
-                RPNSymbol::GetProp => self.run_get_prop(task, &dependants_map),
+                RPNSymbol::GetProp => self.run_get_prop(task, dependency_graph),
                 RPNSymbol::GetTag => self.run_get_tag(task),
                 RPNSymbol::GreaterThan => self.run_greater_than(),
                 RPNSymbol::LessThan => self.run_less_than(),
@@ -0,0 +1,90 @@
+use crate::dependencies::DependencyGraph;
+use crate::error::*;
+use crate::task::Task;
+use chrono::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// The state of a task, derived from its fields and the current time
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pending,
+    /// the task has open blockers
+    Blocked,
+    /// the task is waiting or snoozed until some time in the future
+    Waiting,
+    Done,
+    Deleted,
+}
+
+impl Status {
+    pub const ALL: [Status; 5] = [
+        Status::Pending,
+        Status::Blocked,
+        Status::Waiting,
+        Status::Done,
+        Status::Deleted,
+    ];
+
+    pub fn of(task: &Task, dependency_graph: &DependencyGraph, now: &DateTime<Utc>) -> Self {
+        let is_in_future = |date_time: &Option<DateTime<Utc>>| {
+            date_time.map(|date_time| date_time > *now).unwrap_or(false)
+        };
+
+        if task.is_tombstone() {
+            Status::Deleted
+        } else if task.get_done().is_some() {
+            Status::Done
+        } else if is_in_future(task.get_wait()) || is_in_future(task.get_snooze()) {
+            Status::Waiting
+        } else if dependency_graph.get_open_blockers(task).next().is_some() {
+            Status::Blocked
+        } else {
+            Status::Pending
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Pending => "pending",
+            Status::Blocked => "blocked",
+            Status::Waiting => "waiting",
+            Status::Done => "done",
+            Status::Deleted => "deleted",
+        }
+    }
+
+    /// The number that programs get for the status with `status :`, each status is also in the
+    /// environment by name, so `status : waiting $ =` checks if a task is waiting
+    pub fn to_number(self) -> f64 {
+        match self {
+            Status::Pending => 1.0,
+            Status::Blocked => 2.0,
+            Status::Waiting => 3.0,
+            Status::Done => 4.0,
+            Status::Deleted => 5.0,
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl FromStr for Status {
+    type Err = HyperTaskError;
+
+    fn from_str(s: &str) -> HyperTaskResult<Self> {
+        Status::ALL
+            .iter()
+            .find(|status| status.name() == s)
+            .cloned()
+            .ok_or_else(|| {
+                HyperTaskError::new(HyperTaskErrorDomain::Input, HyperTaskErrorAction::Parse)
+                    .with_msg(|| format!("`{}` is not a valid status", s))
+            })
+    }
+}
@@ -1,7 +1,40 @@
 use super::Task;
 use crate::error::*;
+use crate::interval::normalize_intervals;
 
 impl Task {
+    /// Keeps the notes from both copies of a task, so that notes added on different devices are
+    /// not lost, a note that was removed on either device stays removed
+    fn with_annotations_from(mut self, other: &Task) -> Task {
+        for annotation in &other.annotations {
+            match self
+                .annotations
+                .iter_mut()
+                .find(|own| own.is_same_note(annotation))
+            {
+                Some(own) => {
+                    own.removed_at = match (own.removed_at, annotation.removed_at) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    };
+                }
+                None => self.annotations.push(annotation.clone()),
+            }
+        }
+
+        self.annotations.sort();
+        self
+    }
+
+    /// Keeps the work intervals from both copies of a task, an interval that was stopped on one
+    /// device is stopped in the result
+    fn with_intervals_from(mut self, other: &Task) -> Task {
+        self.intervals.extend(other.intervals.iter().cloned());
+
+        normalize_intervals(&mut self.intervals);
+        self
+    }
+
     pub fn resolve_task_conflict(
         lhs: Option<Task>,
         rhs: Option<Task>,
@@ -19,10 +52,36 @@ impl Task {
                     .msg("tried to resolve a conflict between two different tasks"));
                 };
 
-                if t1.updated_at > t2.updated_at {
-                    Ok(Some(t1))
-                } else {
-                    Ok(Some(t2))
+                match (t1.get_deleted_at(), t2.get_deleted_at()) {
+                    // a deletion only loses to an edit that was made after it
+                    (Some(deleted_at), None) => {
+                        if t2.updated_at > *deleted_at {
+                            Ok(Some(t2))
+                        } else {
+                            Ok(Some(t1))
+                        }
+                    }
+                    (None, Some(deleted_at)) => {
+                        if t1.updated_at > *deleted_at {
+                            Ok(Some(t1))
+                        } else {
+                            Ok(Some(t2))
+                        }
+                    }
+                    (None, None) => {
+                        if t1.updated_at > t2.updated_at {
+                            Ok(Some(t1.with_annotations_from(&t2).with_intervals_from(&t2)))
+                        } else {
+                            Ok(Some(t2.with_annotations_from(&t1).with_intervals_from(&t1)))
+                        }
+                    }
+                    _ => {
+                        if t1.updated_at > t2.updated_at {
+                            Ok(Some(t1))
+                        } else {
+                            Ok(Some(t2))
+                        }
+                    }
                 }
             }
         }
@@ -32,7 +91,10 @@ impl Task {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::annotation::Annotation;
+    use crate::engine::Mutation;
     use crate::id::Id;
+    use crate::interval::WorkInterval;
     use chrono::prelude::*;
     use std::rc::Rc;
 
@@ -126,5 +188,180 @@ mod tests {
                 Task::resolve_task_conflict(Some(task_1.clone()), Some(task_2.clone()))
             );
         }
+
+        #[test]
+        fn when_one_is_deleted_returns_tombstone_unless_edited_after_deletion() {
+            let task = Task {
+                id: Rc::new(Id("test_id_1".to_owned())),
+                updated_at: Utc.ymd(2016, 11, 15).and_hms(9, 10, 11),
+
+                ..Task::generate(&Utc.ymd(2015, 11, 15).and_hms(9, 10, 11))
+            };
+
+            let tombstone = task.to_tombstone(&Utc.ymd(2017, 11, 15).and_hms(9, 10, 11));
+
+            assert_eq!(
+                Ok(Some(tombstone.clone())),
+                Task::resolve_task_conflict(Some(task.clone()), Some(tombstone.clone()))
+            );
+            assert_eq!(
+                Ok(Some(tombstone.clone())),
+                Task::resolve_task_conflict(Some(tombstone.clone()), Some(task.clone()))
+            );
+
+            let edited_task = Task {
+                updated_at: Utc.ymd(2018, 11, 15).and_hms(9, 10, 11),
+
+                ..task
+            };
+
+            assert_eq!(
+                Ok(Some(edited_task.clone())),
+                Task::resolve_task_conflict(Some(edited_task.clone()), Some(tombstone.clone()))
+            );
+            assert_eq!(
+                Ok(Some(edited_task.clone())),
+                Task::resolve_task_conflict(Some(tombstone.clone()), Some(edited_task.clone()))
+            );
+        }
+
+        #[test]
+        fn when_both_are_not_deleted_keeps_annotations_from_both() {
+            let annotation = |day, text: &str| Annotation {
+                created_at: Utc.ymd(2016, 11, day).and_hms(9, 10, 11),
+                text: text.to_owned(),
+                removed_at: None,
+            };
+
+            let task_1 = Task {
+                id: Rc::new(Id("test_id_1".to_owned())),
+                updated_at: Utc.ymd(2016, 11, 15).and_hms(9, 10, 11),
+                annotations: vec![annotation(1, "shared"), annotation(15, "from laptop")],
+
+                ..Task::generate(&Utc.ymd(2015, 11, 15).and_hms(9, 10, 11))
+            };
+
+            let task_2 = Task {
+                updated_at: Utc.ymd(2016, 11, 16).and_hms(9, 10, 11),
+                annotations: vec![annotation(1, "shared"), annotation(16, "from phone")],
+
+                ..task_1.clone()
+            };
+
+            let resolved = Task::resolve_task_conflict(Some(task_1), Some(task_2))
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(
+                resolved.annotations,
+                vec![
+                    annotation(1, "shared"),
+                    annotation(15, "from laptop"),
+                    annotation(16, "from phone")
+                ]
+            );
+        }
+
+        #[test]
+        fn when_both_are_not_deleted_keeps_removed_annotations_removed() {
+            let unchanged = Task {
+                id: Rc::new(Id("test_id_1".to_owned())),
+                annotations: vec![Annotation {
+                    created_at: Utc.ymd(2016, 11, 1).and_hms(9, 10, 11),
+                    text: "call back".to_owned(),
+                    removed_at: None,
+                }],
+
+                ..Task::generate(&Utc.ymd(2016, 11, 1).and_hms(9, 10, 11))
+            };
+
+            let denotated = unchanged.apply_mutations(
+                &[Mutation::Denotate("call".to_owned())],
+                &Utc.ymd(2016, 11, 2).and_hms(9, 10, 11),
+            );
+            // edited on the other device after the note was removed, but without seeing it
+            let edited = unchanged.apply_mutations(
+                &[Mutation::Annotate("called".to_owned())],
+                &Utc.ymd(2016, 11, 3).and_hms(9, 10, 11),
+            );
+
+            let resolved = Task::resolve_task_conflict(Some(unchanged), Some(denotated.clone()))
+                .unwrap()
+                .unwrap();
+            assert!(resolved.get_annotations().is_empty());
+
+            let resolved = Task::resolve_task_conflict(Some(edited), Some(denotated))
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                resolved
+                    .get_annotations()
+                    .iter()
+                    .map(|annotation| annotation.text.as_str())
+                    .collect::<Vec<&str>>(),
+                vec!["called"]
+            );
+        }
+
+        #[test]
+        fn when_both_are_not_deleted_merges_work_intervals() {
+            let at = |hour| Utc.ymd(2016, 11, 15).and_hms(hour, 0, 0);
+
+            // started on the laptop, then stopped on the phone before syncing
+            let task_1 = Task {
+                id: Rc::new(Id("test_id_1".to_owned())),
+                updated_at: at(9),
+                intervals: vec![WorkInterval {
+                    started_at: at(9),
+                    stopped_at: None,
+                }],
+
+                ..Task::generate(&Utc.ymd(2015, 11, 15).and_hms(9, 10, 11))
+            };
+
+            let task_2 = Task {
+                updated_at: at(10),
+                intervals: vec![WorkInterval {
+                    started_at: at(9),
+                    stopped_at: Some(at(10)),
+                }],
+
+                ..task_1.clone()
+            };
+
+            let task_1 = Task {
+                updated_at: at(11),
+                intervals: vec![
+                    WorkInterval {
+                        started_at: at(9),
+                        stopped_at: None,
+                    },
+                    WorkInterval {
+                        started_at: at(11),
+                        stopped_at: None,
+                    },
+                ],
+
+                ..task_1
+            };
+
+            let resolved = Task::resolve_task_conflict(Some(task_1), Some(task_2))
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(
+                resolved.intervals,
+                vec![
+                    WorkInterval {
+                        started_at: at(9),
+                        stopped_at: Some(at(10)),
+                    },
+                    WorkInterval {
+                        started_at: at(11),
+                        stopped_at: None,
+                    },
+                ]
+            );
+        }
     }
 }
@@ -1,10 +1,14 @@
+use crate::annotation::Annotation;
+use crate::attribute::AttributeValue;
 use crate::id::Id;
+use crate::interval::WorkInterval;
 use crate::recur::Recur;
 use chrono::prelude::*;
-use serde::{Deserialize, Serialize, Serializer};
-use std::collections::HashSet;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashSet};
 use std::hash::{Hash, Hasher};
 use std::rc::Rc;
+use time::Duration;
 
 #[allow(clippy::derive_hash_xor_eq)]
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -14,17 +18,31 @@ pub struct Task {
     pub(super) id: Rc<Id>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub(super) description: Option<String>,
+    #[serde(default)]
+    pub(super) deleted_at: Option<DateTime<Utc>>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub(super) blocked_by: Option<Rc<Id>>,
+    pub(super) description: Option<String>,
+    #[serde(serialize_with = "ordered_id_set")]
+    #[serde(deserialize_with = "single_id_or_id_set")]
+    #[serde(skip_serializing_if = "HashSet::is_empty")]
+    #[serde(default)]
+    pub(super) blocked_by: HashSet<Rc<Id>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(super) done: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(super) due: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub(super) parent: Option<Rc<Id>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub(super) recur: Option<Recur>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(super) snooze: Option<DateTime<Utc>>,
+    /// the first task of the recurring series that this task is an instance of
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub(super) template: Option<Rc<Id>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(super) wait: Option<DateTime<Utc>>,
 
@@ -32,6 +50,21 @@ pub struct Task {
     #[serde(skip_serializing_if = "HashSet::is_empty")]
     #[serde(default)]
     pub(super) tags: HashSet<String>,
+
+    /// notes about the task, ordered from oldest to newest
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub(super) annotations: Vec<Annotation>,
+
+    /// the periods of time spent working on the task, ordered from oldest to newest
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub(super) intervals: Vec<WorkInterval>,
+
+    /// user defined attributes, these are declared in the config
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    #[serde(default)]
+    pub(super) attributes: BTreeMap<String, AttributeValue>,
 }
 
 fn ordered_set<S>(value: &HashSet<String>, serializer: S) -> Result<S::Ok, S::Error>
@@ -45,46 +78,141 @@ where
     vec.serialize(serializer)
 }
 
+fn ordered_id_set<S>(value: &HashSet<Rc<Id>>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut vec = value.iter().map(|id| &id.0).collect::<Vec<&String>>();
+
+    vec.sort();
+
+    vec.serialize(serializer)
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SingleIdOrIdSet {
+    Single(Rc<Id>),
+    Set(HashSet<Rc<Id>>),
+}
+
+/// Tasks used to only be able to have a single blocker, so old task files store a single id
+fn single_id_or_id_set<'de, D>(deserializer: D) -> Result<HashSet<Rc<Id>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match SingleIdOrIdSet::deserialize(deserializer)? {
+        SingleIdOrIdSet::Single(id) => {
+            let mut set = HashSet::new();
+            set.insert(id);
+            set
+        }
+        SingleIdOrIdSet::Set(set) => set,
+    })
+}
+
 #[allow(clippy::derive_hash_xor_eq)]
 impl Hash for Task {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.created_at.hash(state);
-        self.blocked_by.hash(state);
+        let mut blocked_by_vec: Vec<&Rc<Id>> = self.blocked_by.iter().collect();
+        blocked_by_vec.sort_by(|a, b| a.0.cmp(&b.0));
+        blocked_by_vec.hash(state);
+
+        self.deleted_at.hash(state);
         self.description.hash(state);
         self.done.hash(state);
         self.due.hash(state);
         self.id.hash(state);
+        self.parent.hash(state);
         self.recur.hash(state);
         self.snooze.hash(state);
+        self.template.hash(state);
         self.updated_at.hash(state);
         self.wait.hash(state);
 
         let mut tags_vec: Vec<&String> = self.tags.iter().collect();
         tags_vec.sort();
         tags_vec.hash(state);
+
+        self.attributes.hash(state);
+        self.annotations.hash(state);
+        self.intervals.hash(state);
     }
 }
 
 impl Task {
     pub fn generate(now: &DateTime<Utc>) -> Self {
+        Self::new(Id::generate(), now)
+    }
+
+    /// Creates an empty task with a known id
+    pub fn new(id: Id, now: &DateTime<Utc>) -> Self {
         Self {
+            annotations: vec![],
+            attributes: BTreeMap::new(),
             created_at: *now,
-            blocked_by: None,
+            blocked_by: HashSet::new(),
+            deleted_at: None,
             description: None,
             done: None,
             due: None,
-            id: Rc::new(Id::generate()),
+            id: Rc::new(id),
+            intervals: vec![],
+            parent: None,
             recur: None,
             snooze: None,
             tags: HashSet::new(),
+            template: None,
             updated_at: *now,
             wait: None,
         }
     }
 
+    /// Creates a tombstone recording that this task was deleted at `now`.
+    /// Only the id and timestamps are kept, so that the deletion can be synced and later
+    /// garbage collected without holding on to the task's content.
+    pub fn to_tombstone(&self, now: &DateTime<Utc>) -> Self {
+        Self {
+            annotations: vec![],
+            attributes: BTreeMap::new(),
+            created_at: self.created_at,
+            blocked_by: HashSet::new(),
+            deleted_at: Some(*now),
+            description: None,
+            done: None,
+            due: None,
+            id: self.id.clone(),
+            intervals: vec![],
+            parent: None,
+            recur: None,
+            snooze: None,
+            tags: HashSet::new(),
+            template: None,
+            updated_at: *now,
+            wait: None,
+        }
+    }
+
+    /// Creates a copy of an earlier version of the task, marked as updated at `now` so that it
+    /// wins any sync conflicts with the version it replaces
+    pub fn to_restored(&self, now: &DateTime<Utc>) -> Self {
+        Self {
+            updated_at: *now,
+            ..self.clone()
+        }
+    }
+
+    pub fn is_tombstone(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
     pub fn get_created_at(&self) -> &DateTime<Utc> {
         &self.created_at
     }
+    pub fn get_deleted_at(&self) -> &Option<DateTime<Utc>> {
+        &self.deleted_at
+    }
     pub fn get_description(&self) -> &Option<String> {
         &self.description
     }
@@ -97,8 +225,11 @@ impl Task {
     pub fn get_id(&self) -> Rc<Id> {
         self.id.clone()
     }
-    pub fn get_blocked_by(&self) -> Option<Rc<Id>> {
-        self.blocked_by.clone()
+    pub fn get_blocked_by(&self) -> &HashSet<Rc<Id>> {
+        &self.blocked_by
+    }
+    pub fn get_parent(&self) -> &Option<Rc<Id>> {
+        &self.parent
     }
     pub fn get_recur(&self) -> &Option<Recur> {
         &self.recur
@@ -106,6 +237,9 @@ impl Task {
     pub fn get_snooze(&self) -> &Option<DateTime<Utc>> {
         &self.snooze
     }
+    pub fn get_template(&self) -> &Option<Rc<Id>> {
+        &self.template
+    }
     pub fn get_tags(&self) -> &HashSet<String> {
         &self.tags
     }
@@ -115,4 +249,48 @@ impl Task {
     pub fn get_wait(&self) -> &Option<DateTime<Utc>> {
         &self.wait
     }
+    /// The notes on the task, without the ones that have been removed
+    pub fn get_annotations(&self) -> Vec<&Annotation> {
+        self.annotations
+            .iter()
+            .filter(|annotation| !annotation.is_removed())
+            .collect()
+    }
+    pub fn get_attributes(&self) -> &BTreeMap<String, AttributeValue> {
+        &self.attributes
+    }
+    pub fn get_intervals(&self) -> &[WorkInterval] {
+        &self.intervals
+    }
+
+    /// A task is active while it's being worked on, between being started and stopped
+    pub fn is_active(&self) -> bool {
+        self.intervals
+            .last()
+            .map(|interval| interval.is_active())
+            .unwrap_or(false)
+    }
+
+    /// The total time spent working on the task
+    pub fn get_time_spent(&self, now: &DateTime<Utc>) -> Duration {
+        self.intervals
+            .iter()
+            .fold(Duration::zero(), |total, interval| {
+                total + interval.duration(now)
+            })
+    }
+
+    /// The total time spent working on the task within `from` and `to`
+    pub fn get_time_spent_between(
+        &self,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+        now: &DateTime<Utc>,
+    ) -> Duration {
+        self.intervals
+            .iter()
+            .fold(Duration::zero(), |total, interval| {
+                total + interval.duration_between(from, to, now)
+            })
+    }
 }
@@ -0,0 +1,199 @@
+use super::Task;
+use chrono::prelude::*;
+use std::fmt;
+use std::rc::Rc;
+
+/// A single field of a task that a command would change, `None` means the field is unset
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.field,
+            self.before.as_deref().unwrap_or("none"),
+            self.after.as_deref().unwrap_or("none")
+        )
+    }
+}
+
+fn format_date_time(date_time: &Option<DateTime<Utc>>) -> Option<String> {
+    date_time.map(|date_time| date_time.format("%Y-%m-%d %H:%M").to_string())
+}
+
+fn format_list<T: fmt::Display>(items: impl Iterator<Item = T>, separator: &str) -> Option<String> {
+    let mut items: Vec<String> = items.map(|item| item.to_string()).collect();
+
+    if items.is_empty() {
+        None
+    } else {
+        items.sort();
+        Some(items.join(separator))
+    }
+}
+
+impl Task {
+    /// Each field as it would be shown in a diff, other than the `updated_at` time which changes
+    /// with every edit
+    fn get_diff_fields(&self) -> Vec<(String, Option<String>)> {
+        let mut fields = vec![
+            ("deleted_at".to_string(), format_date_time(&self.deleted_at)),
+            ("description".to_string(), self.description.clone()),
+            (
+                "blocked".to_string(),
+                format_list(self.blocked_by.iter(), " "),
+            ),
+            ("done".to_string(), format_date_time(&self.done)),
+            ("due".to_string(), format_date_time(&self.due)),
+            (
+                "parent".to_string(),
+                self.parent.as_ref().map(|id| id.to_string()),
+            ),
+            (
+                "recur".to_string(),
+                self.recur.as_ref().map(|recur| recur.to_string()),
+            ),
+            ("snooze".to_string(), format_date_time(&self.snooze)),
+            (
+                "template".to_string(),
+                self.template.as_ref().map(|id| id.to_string()),
+            ),
+            ("wait".to_string(), format_date_time(&self.wait)),
+            (
+                "tags".to_string(),
+                format_list(self.tags.iter().map(|tag| format!("+{}", tag)), " "),
+            ),
+            (
+                "annotations".to_string(),
+                format_list(self.get_annotations().into_iter(), "; "),
+            ),
+            (
+                "intervals".to_string(),
+                format_list(
+                    self.intervals.iter().map(|interval| {
+                        format!(
+                            "{}..{}",
+                            interval.started_at.format("%Y-%m-%d %H:%M"),
+                            format_date_time(&interval.stopped_at)
+                                .unwrap_or_else(|| "now".to_string())
+                        )
+                    }),
+                    "; ",
+                ),
+            ),
+        ];
+
+        for (name, value) in &self.attributes {
+            fields.push((name.to_string(), Some(value.to_string())));
+        }
+
+        fields
+    }
+
+    /// Lists the fields that differ between `before` and this task, `before` is `None` when the
+    /// task is new. This is used to preview what a command would change before it's written
+    pub fn diff_from(&self, before: Option<&Task>) -> Vec<FieldChange> {
+        let before_fields = before
+            .map(|task| task.get_diff_fields())
+            .unwrap_or_default();
+        let after_fields = self.get_diff_fields();
+
+        let mut changes: Vec<FieldChange> = vec![];
+
+        for (field, after) in &after_fields {
+            let before = before_fields
+                .iter()
+                .find(|(before_field, _)| before_field == field)
+                .and_then(|(_, value)| value.clone());
+
+            if before != *after {
+                changes.push(FieldChange {
+                    field: field.to_string(),
+                    before,
+                    after: after.clone(),
+                });
+            }
+        }
+
+        // user defined attributes that have been removed
+        for (field, before) in &before_fields {
+            if before.is_some()
+                && !after_fields
+                    .iter()
+                    .any(|(after_field, _)| after_field == field)
+            {
+                changes.push(FieldChange {
+                    field: field.to_string(),
+                    before: before.clone(),
+                    after: None,
+                });
+            }
+        }
+
+        changes
+    }
+}
+
+/// The changes that a command would make to one task
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TaskDiff {
+    pub id: String,
+    pub is_new: bool,
+    pub changes: Vec<FieldChange>,
+}
+
+impl TaskDiff {
+    pub fn new(before: Option<&Task>, after: &Rc<Task>) -> Self {
+        Self {
+            id: after.get_id().to_string(),
+            is_new: before.is_none(),
+            changes: after.diff_from(before),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id::Id;
+
+    #[test]
+    fn only_lists_changed_fields() {
+        let before = Task {
+            id: Rc::new(Id("test_id".to_owned())),
+            description: Some("write report".to_owned()),
+
+            ..Task::generate(&Utc.ymd(2015, 11, 15).and_hms(9, 10, 11))
+        };
+
+        let after = Task {
+            due: Some(Utc.ymd(2015, 11, 20).and_hms(17, 0, 0)),
+            tags: vec!["work".to_owned()].into_iter().collect(),
+            updated_at: Utc.ymd(2015, 11, 16).and_hms(9, 10, 11),
+
+            ..before.clone()
+        };
+
+        assert_eq!(
+            after.diff_from(Some(&before)),
+            vec![
+                FieldChange {
+                    field: "due".to_owned(),
+                    before: None,
+                    after: Some("2015-11-20 17:00".to_owned()),
+                },
+                FieldChange {
+                    field: "tags".to_owned(),
+                    before: None,
+                    after: Some("+work".to_owned()),
+                },
+            ]
+        );
+    }
+}
@@ -27,22 +27,28 @@ mod test {
     use super::*;
     use crate::id::Id;
     use chrono::prelude::*;
-    use std::collections::HashSet;
+    use std::collections::{BTreeMap, HashSet};
     use std::rc::Rc;
     use wasm_bindgen_test::*;
 
     #[wasm_bindgen_test]
     fn does_hash_to_consistent_value() {
         let task = Task {
+            annotations: vec![],
+            attributes: BTreeMap::new(),
             created_at: Utc.ymd(2014, 7, 8).and_hms(9, 10, 11),
-            blocked_by: None,
+            blocked_by: HashSet::new(),
+            deleted_at: None,
             description: None,
             done: None,
             due: None,
             id: Rc::new(Id("test_id".into())),
+            intervals: vec![],
+            parent: None,
             recur: None,
             snooze: None,
             tags: HashSet::new(),
+            template: None,
             updated_at: Utc.ymd(2014, 7, 8).and_hms(9, 10, 11),
             wait: None,
         };
@@ -53,15 +59,21 @@ mod test {
     #[wasm_bindgen_test]
     fn hashes_different_tasks_to_different_values() {
         let task = Task {
+            annotations: vec![],
+            attributes: BTreeMap::new(),
             created_at: Utc.ymd(2014, 7, 8).and_hms(9, 10, 11),
-            blocked_by: None,
+            blocked_by: HashSet::new(),
+            deleted_at: None,
             description: Some("with a description".into()),
             done: None,
             due: None,
             id: Rc::new(Id("test_id".into())),
+            intervals: vec![],
+            parent: None,
             recur: None,
             snooze: None,
             tags: HashSet::new(),
+            template: None,
             updated_at: Utc.ymd(2014, 7, 8).and_hms(9, 10, 11),
             wait: None,
         };
@@ -1,8 +1,10 @@
 mod conflict;
 mod data;
+mod diff;
 mod hash;
 mod query_mutation;
 
 pub use data::*;
+pub use diff::{FieldChange, TaskDiff};
 
 pub type Score = f64;
@@ -1,54 +1,146 @@
-use crate::engine::{Mutation, Query};
+use crate::annotation::Annotation;
+use crate::engine::Mutation;
+use crate::id::Id;
+use crate::interval::WorkInterval;
 use crate::prop::Prop;
+use crate::query::{Query, QueryAttribute};
+use crate::recur::RecurAnchor;
 use crate::tag::{Sign, Tag};
 use chrono::prelude::*;
+use std::cmp::Ordering;
 use std::rc::Rc;
-use time::Duration;
 
 impl super::Task {
+    /// Checks that the task satisfies every one of `queries`, an empty list matches nothing
     pub fn satisfies_queries(&self, queries: &[Query]) -> bool {
         if queries.is_empty() {
             return false;
         }
 
-        let mut default = false;
+        queries.iter().all(|query| self.satisfies_query(query))
+    }
 
-        for query in queries {
-            match &query {
-                Query::Id(id) => {
-                    if id.sub_eq(&self.id) {
-                        return true;
-                    } else {
-                        continue;
-                    }
-                }
+    pub fn satisfies_query(&self, query: &Query) -> bool {
+        match query {
+            Query::Id(id) => id.is_prefix_of(&self.id),
+            Query::ExactId(id) => *id == *self.id,
 
-                Query::Tag(Tag {
-                    sign: Sign::Plus,
-                    name,
-                }) => {
-                    if self.tags.contains(name) {
-                        return true;
-                    } else {
-                        continue;
-                    }
-                }
+            Query::Tag(Tag {
+                sign: Sign::Plus,
+                name,
+            }) => self.tags.contains(name),
 
-                Query::Tag(Tag {
-                    sign: Sign::Minus,
-                    name,
-                }) => {
-                    if self.tags.contains(name) {
-                        return false;
-                    } else {
-                        default = true;
-                        continue;
-                    }
-                }
+            Query::Tag(Tag {
+                sign: Sign::Minus,
+                name,
+            }) => !self.tags.contains(name),
+
+            Query::Description(text) => self
+                .description
+                .as_ref()
+                .map(|description| description.contains(text.as_str()))
+                .unwrap_or(false),
+
+            Query::Word(word) => self
+                .description
+                .as_ref()
+                .map(|description| {
+                    description
+                        .split(|c: char| !c.is_alphanumeric())
+                        .any(|w| w.to_lowercase() == word.to_lowercase())
+                })
+                .unwrap_or(false),
+
+            Query::Regex(regex) => self
+                .description
+                .as_ref()
+                .map(|description| regex.is_match(description))
+                .unwrap_or(false),
+
+            Query::Has(attribute) => self.has_attribute(*attribute),
+
+            Query::Before(attribute, date_time) => self
+                .get_date_attribute(*attribute)
+                .map(|d| d < *date_time)
+                .unwrap_or(false),
+
+            Query::After(attribute, date_time) => self
+                .get_date_attribute(*attribute)
+                .map(|d| d > *date_time)
+                .unwrap_or(false),
+
+            Query::BlockedBy(id) => self
+                .blocked_by
+                .iter()
+                .any(|blocked_by| id.is_prefix_of(blocked_by)),
+
+            Query::Parent(id) => self
+                .parent
+                .as_ref()
+                .map(|parent| id.is_prefix_of(parent))
+                .unwrap_or(false),
+
+            // without the rest of the tasks we can only see one level down
+            Query::Subtree(id) => {
+                id.is_prefix_of(&self.id) || self.satisfies_query(&Query::Parent(id.clone()))
             }
+
+            Query::Template(id) => self
+                .template
+                .as_ref()
+                .map(|template| id.is_prefix_of(template))
+                .unwrap_or_else(|| id.is_prefix_of(&self.id) && self.recur.is_some()),
+
+            // without the rest of the tasks and the time we can't tell the status
+            Query::Status(_) => false,
+
+            Query::HasAttribute(name) => self.attributes.contains_key(name),
+
+            Query::AttributeEq(name, value) => self.attributes.get(name) == Some(value),
+
+            Query::AttributeBelow(name, value) => self
+                .attributes
+                .get(name)
+                .and_then(|v| v.compare(value))
+                .map(|ordering| ordering == Ordering::Less)
+                .unwrap_or(false),
+
+            Query::AttributeAbove(name, value) => self
+                .attributes
+                .get(name)
+                .and_then(|v| v.compare(value))
+                .map(|ordering| ordering == Ordering::Greater)
+                .unwrap_or(false),
+
+            Query::And(queries) => queries.iter().all(|q| self.satisfies_query(q)),
+            Query::Or(queries) => queries.iter().any(|q| self.satisfies_query(q)),
+            Query::Not(q) => !self.satisfies_query(q),
+        }
+    }
+
+    fn has_attribute(&self, attribute: QueryAttribute) -> bool {
+        match attribute {
+            QueryAttribute::Description => self.description.is_some(),
+            QueryAttribute::Blocked => !self.blocked_by.is_empty(),
+            QueryAttribute::Parent => self.parent.is_some(),
+            QueryAttribute::Recur => self.recur.is_some(),
+            date_attribute => self.get_date_attribute(date_attribute).is_some(),
         }
+    }
 
-        default
+    fn get_date_attribute(&self, attribute: QueryAttribute) -> Option<DateTime<Utc>> {
+        match attribute {
+            QueryAttribute::CreatedAt => Some(self.created_at),
+            QueryAttribute::UpdatedAt => Some(self.updated_at),
+            QueryAttribute::Done => self.done,
+            QueryAttribute::Due => self.due,
+            QueryAttribute::Snooze => self.snooze,
+            QueryAttribute::Wait => self.wait,
+            QueryAttribute::Description
+            | QueryAttribute::Blocked
+            | QueryAttribute::Parent
+            | QueryAttribute::Recur => None,
+        }
     }
 
     pub fn apply_mutations(&self, mutations: &[Mutation], now: &DateTime<Utc>) -> Self {
@@ -61,6 +153,67 @@ impl super::Task {
         new_task
     }
 
+    /// Applies mutations like `apply_mutations`, but when a recurring task is done it's closed,
+    /// keeping its dates as a record of the completion, and the next occurrence is returned as a
+    /// new instance of the same template
+    pub fn apply_mutations_spawning_instances(
+        &self,
+        mutations: &[Mutation],
+        now: &DateTime<Utc>,
+    ) -> (Self, Option<Self>) {
+        let updated = self.apply_mutations(mutations, now);
+
+        let done = mutations.iter().find_map(|m| match m {
+            Mutation::SetProp(Prop::Done(done)) => Some(*done),
+            _ => None,
+        });
+
+        // `apply_mutations` moves the dates of the task forward if it has another occurrence
+        let next_occurrence = match (done, &self.recur, self.done, updated.done) {
+            (Some(done), Some(_), None, None) => updated.due.or(updated.wait).map(|_| done),
+            _ => None,
+        };
+
+        match next_occurrence {
+            Some(done) => {
+                let template = self.template.clone().unwrap_or_else(|| self.id.clone());
+                // the occurrence that was closed is the same on every device, unlike the next
+                // one, which depends on when the task was done if it recurs from completion
+                let closed_occurrence = self.due.or(self.wait).unwrap_or(self.created_at);
+
+                let next = Self {
+                    annotations: vec![],
+                    created_at: *now,
+                    id: Rc::new(Id::for_occurrence(&template, &closed_occurrence)),
+                    intervals: vec![],
+                    snooze: None,
+                    template: Some(template.clone()),
+                    ..updated.clone()
+                };
+
+                let completed = Self {
+                    done: Some(done),
+                    due: self.due,
+                    recur: None,
+                    template: Some(template),
+                    wait: self.wait,
+                    ..updated
+                };
+
+                (completed, Some(next))
+            }
+            None => (updated, None),
+        }
+    }
+
+    fn stop_intervals(&mut self, now: &DateTime<Utc>) {
+        for interval in self.intervals.iter_mut() {
+            if interval.is_active() {
+                interval.stopped_at = Some(*now);
+            }
+        }
+    }
+
     pub fn apply_mutation(&mut self, mutation: &Mutation, now: &DateTime<Utc>) -> &Self {
         match mutation {
             Mutation::SetTag(Tag {
@@ -75,21 +228,55 @@ impl super::Task {
             }) => {
                 self.tags.remove(name);
             }
+            Mutation::Annotate(text) => {
+                self.annotations.push(Annotation {
+                    created_at: *now,
+                    text: text.to_string(),
+                    removed_at: None,
+                });
+                self.annotations.sort();
+            }
+            Mutation::Denotate(text) => {
+                for annotation in self.annotations.iter_mut() {
+                    if !annotation.is_removed() && annotation.text.contains(text.as_str()) {
+                        annotation.removed_at = Some(*now);
+                    }
+                }
+            }
+            Mutation::Start => {
+                if !self.is_active() {
+                    self.intervals.push(WorkInterval {
+                        started_at: *now,
+                        stopped_at: None,
+                    });
+                }
+            }
+            Mutation::Stop => self.stop_intervals(now),
             Mutation::SetProp(Prop::Description(description)) => {
                 self.description = Some(description.to_string());
             }
             Mutation::SetProp(Prop::Done(done)) => {
-                if let Some(recur) = &self.recur {
-                    let dt: Duration = Duration::from(recur);
+                self.stop_intervals(now);
 
-                    if let Some(due) = self.due {
-                        self.due = Some(due + dt);
-                    }
-                    if let Some(wait) = self.wait {
-                        self.wait = Some(wait + dt);
+                let next_occurrence = self.recur.as_ref().and_then(|recur| {
+                    self.due.or(self.wait).map(|anchor| match recur.anchor {
+                        RecurAnchor::Due => (anchor, recur.next(&anchor)),
+                        RecurAnchor::Done => (anchor, recur.next(done)),
+                    })
+                });
+
+                match (&self.recur, next_occurrence) {
+                    // the wait date keeps the same offset from the due date
+                    (Some(recur), Some((anchor, Some(next)))) => {
+                        let offset = next - anchor;
+
+                        self.due = self.due.map(|due| due + offset);
+                        self.wait = self.wait.map(|wait| wait + offset);
+                        self.recur = Some(recur.after_occurrence(&anchor));
                     }
-                } else {
-                    self.done = Some(*done);
+                    // recurring tasks without dates have nothing to move forward
+                    (Some(_), None) => {}
+                    _ => self.done = Some(*done),
                 }
             }
             Mutation::SetProp(Prop::Due(due)) => {
@@ -102,8 +289,33 @@ impl super::Task {
                 self.wait = *wait;
             }
             Mutation::SetProp(Prop::Recur(recur)) => self.recur = recur.clone(),
+            Mutation::SetProp(Prop::RecurUntil(until)) => {
+                if let Some(recur) = &mut self.recur {
+                    recur.until = *until;
+                }
+            }
+            Mutation::SetProp(Prop::RecurCount(count)) => {
+                if let Some(recur) = &mut self.recur {
+                    recur.count = *count;
+                }
+            }
             Mutation::SetProp(Prop::Blocked(blocked)) => {
-                self.blocked_by = blocked.as_ref().map(|d| Rc::new(d.clone()));
+                self.blocked_by = blocked.iter().map(|id| Rc::new(id.clone())).collect();
+            }
+            Mutation::SetProp(Prop::Blocker(Sign::Plus, id)) => {
+                self.blocked_by.insert(Rc::new(id.clone()));
+            }
+            Mutation::SetProp(Prop::Blocker(Sign::Minus, id)) => {
+                self.blocked_by.remove(id);
+            }
+            Mutation::SetProp(Prop::Attribute(name, Some(value))) => {
+                self.attributes.insert(name.to_string(), value.clone());
+            }
+            Mutation::SetProp(Prop::Attribute(name, None)) => {
+                self.attributes.remove(name);
+            }
+            Mutation::SetProp(Prop::Parent(parent)) => {
+                self.parent = parent.as_ref().map(|id| Rc::new(id.clone()));
             }
         }
 
@@ -112,3 +324,237 @@ impl super::Task {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attribute::AttributeValue;
+    use crate::id::Id;
+    use crate::query::DescriptionRegex;
+    use crate::task::Task;
+
+    fn tag(sign: Sign, name: &str) -> Query {
+        Query::Tag(Tag {
+            sign,
+            name: name.to_owned(),
+        })
+    }
+
+    fn create_task(tags: &[&str]) -> Task {
+        Task {
+            id: Rc::new(Id("test_id".to_owned())),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+
+            ..Task::generate(&Utc.ymd(2015, 11, 15).and_hms(9, 10, 11))
+        }
+    }
+
+    mod satisfies_query {
+        use super::*;
+
+        #[test]
+        fn and_requires_every_query() {
+            let query = Query::And(vec![tag(Sign::Plus, "work"), tag(Sign::Plus, "urgent")]);
+
+            assert!(create_task(&["work", "urgent"]).satisfies_query(&query));
+            assert!(!create_task(&["work"]).satisfies_query(&query));
+        }
+
+        #[test]
+        fn or_requires_any_query() {
+            let query = Query::Or(vec![tag(Sign::Plus, "work"), tag(Sign::Plus, "urgent")]);
+
+            assert!(create_task(&["urgent"]).satisfies_query(&query));
+            assert!(!create_task(&["home"]).satisfies_query(&query));
+        }
+
+        #[test]
+        fn not_inverts_query() {
+            let query = Query::Not(Box::new(Query::Or(vec![
+                tag(Sign::Plus, "work"),
+                tag(Sign::Minus, "home"),
+            ])));
+
+            assert!(create_task(&["home"]).satisfies_query(&query));
+            assert!(!create_task(&["work", "home"]).satisfies_query(&query));
+            assert!(!create_task(&[]).satisfies_query(&query));
+        }
+    }
+
+    mod satisfies_description_query {
+        use super::*;
+
+        fn create_task_with_description(description: &str) -> Task {
+            Task {
+                description: Some(description.to_owned()),
+
+                ..create_task(&[])
+            }
+        }
+
+        #[test]
+        fn description_matches_substrings() {
+            let query = Query::Description("voice".to_owned());
+
+            assert!(create_task_with_description("pay the invoice").satisfies_query(&query));
+            assert!(!create_task_with_description("pay the bill").satisfies_query(&query));
+            assert!(!create_task(&[]).satisfies_query(&query));
+        }
+
+        #[test]
+        fn word_matches_whole_words_ignoring_case() {
+            let query = Query::Word("invoice".to_owned());
+
+            assert!(create_task_with_description("Pay the Invoice!").satisfies_query(&query));
+            assert!(!create_task_with_description("pay the invoices").satisfies_query(&query));
+        }
+
+        #[test]
+        fn regex_matches_description() {
+            let query = Query::Regex(DescriptionRegex::new(r"^pay .* #\d+$").unwrap());
+
+            assert!(create_task_with_description("pay invoice #42").satisfies_query(&query));
+            assert!(!create_task_with_description("send invoice #42").satisfies_query(&query));
+        }
+    }
+
+    mod satisfies_attribute_query {
+        use super::*;
+
+        #[test]
+        fn has_matches_set_attributes() {
+            let task = Task {
+                due: Some(Utc.ymd(2016, 1, 1).and_hms(0, 0, 0)),
+
+                ..create_task(&[])
+            };
+
+            assert!(task.satisfies_query(&Query::Has(QueryAttribute::Due)));
+            assert!(task.satisfies_query(&Query::Has(QueryAttribute::CreatedAt)));
+            assert!(!task.satisfies_query(&Query::Has(QueryAttribute::Recur)));
+            assert!(!task.satisfies_query(&Query::Has(QueryAttribute::Done)));
+        }
+
+        #[test]
+        fn before_and_after_compare_dates() {
+            let task = Task {
+                due: Some(Utc.ymd(2016, 1, 1).and_hms(0, 0, 0)),
+
+                ..create_task(&[])
+            };
+
+            let earlier = Utc.ymd(2015, 12, 1).and_hms(0, 0, 0);
+            let later = Utc.ymd(2016, 2, 1).and_hms(0, 0, 0);
+
+            assert!(task.satisfies_query(&Query::Before(QueryAttribute::Due, later)));
+            assert!(!task.satisfies_query(&Query::Before(QueryAttribute::Due, earlier)));
+            assert!(task.satisfies_query(&Query::After(QueryAttribute::Due, earlier)));
+            assert!(!task.satisfies_query(&Query::After(QueryAttribute::Done, earlier)));
+        }
+
+        #[test]
+        fn user_defined_attributes_can_be_set_and_compared() {
+            let now = Utc.ymd(2015, 11, 15).and_hms(9, 10, 11);
+            let estimate = AttributeValue::Duration;
+
+            let task = create_task(&[]).apply_mutations(
+                &[Mutation::SetProp(Prop::Attribute(
+                    "estimate".to_owned(),
+                    Some(estimate(2 * 60 * 60)),
+                ))],
+                &now,
+            );
+
+            assert!(task.satisfies_query(&Query::HasAttribute("estimate".to_owned())));
+            assert!(task.satisfies_query(&Query::AttributeEq(
+                "estimate".to_owned(),
+                estimate(2 * 60 * 60)
+            )));
+            assert!(task.satisfies_query(&Query::AttributeBelow(
+                "estimate".to_owned(),
+                estimate(3 * 60 * 60)
+            )));
+            assert!(!task.satisfies_query(&Query::AttributeAbove(
+                "estimate".to_owned(),
+                estimate(3 * 60 * 60)
+            )));
+
+            let task = task.apply_mutations(
+                &[Mutation::SetProp(Prop::Attribute(
+                    "estimate".to_owned(),
+                    None,
+                ))],
+                &now,
+            );
+
+            assert!(!task.satisfies_query(&Query::HasAttribute("estimate".to_owned())));
+        }
+    }
+
+    mod satisfies_queries {
+        use super::*;
+
+        #[test]
+        fn matches_nothing_when_empty() {
+            assert!(!create_task(&["work"]).satisfies_queries(&[]));
+        }
+
+        #[test]
+        fn requires_every_query() {
+            let queries = vec![tag(Sign::Plus, "work"), tag(Sign::Minus, "urgent")];
+
+            assert!(create_task(&["work"]).satisfies_queries(&queries));
+            assert!(!create_task(&["work", "urgent"]).satisfies_queries(&queries));
+        }
+    }
+
+    mod apply_mutations {
+        use super::*;
+        use crate::recur::{Frequency, Recur};
+        use time::Duration;
+
+        fn complete_late(anchor: RecurAnchor) -> Task {
+            let done = Utc.ymd(2015, 11, 20).and_hms(18, 0, 0);
+
+            Task {
+                due: Some(Utc.ymd(2015, 11, 15).and_hms(18, 0, 0)),
+                recur: Some(Recur {
+                    anchor,
+                    ..Recur::every(Frequency::Day, 3)
+                }),
+
+                ..create_task(&[])
+            }
+            .apply_mutations(&[Mutation::SetProp(Prop::Done(done))], &done)
+        }
+
+        #[test]
+        fn recurs_from_the_due_date_or_the_done_date() {
+            let task = complete_late(RecurAnchor::Due);
+            assert_eq!(task.due, Some(Utc.ymd(2015, 11, 18).and_hms(18, 0, 0)));
+
+            let task = complete_late(RecurAnchor::Done);
+            assert_eq!(task.due, Some(Utc.ymd(2015, 11, 23).and_hms(18, 0, 0)));
+            assert_eq!(task.done, None);
+        }
+
+        #[test]
+        fn start_and_stop_track_time() {
+            let at = |hour| Utc.ymd(2015, 11, 16).and_hms(hour, 0, 0);
+
+            let task = create_task(&[])
+                .apply_mutations(&[Mutation::Start], &at(9))
+                .apply_mutations(&[Mutation::Start], &at(10));
+            assert!(task.is_active());
+            assert_eq!(task.get_time_spent(&at(11)), Duration::hours(2));
+
+            let task = task.apply_mutations(&[Mutation::Stop], &at(12));
+            assert!(!task.is_active());
+            assert_eq!(task.get_time_spent(&at(13)), Duration::hours(3));
+            assert_eq!(
+                task.get_time_spent_between(&at(11), &at(20), &at(20)),
+                Duration::hours(1)
+            );
+        }
+    }
+}
@@ -36,10 +36,19 @@ pub struct CliArgs {
     /// File to store PID in
     #[clap(long)]
     pub pid_file: Option<PathBuf>,
+
+    /// Number of days to keep tombstones of deleted tasks before removing them
+    #[clap(long, env = "HYPERTASK_TOMBSTONE_RETENTION_DAYS")]
+    pub tombstone_retention_days: Option<i64>,
 }
 
 impl hypertask_task_io_operations::ProvidesDataDir for CliArgs {
     fn get_task_state_dir(&self) -> &std::path::PathBuf {
         &self.data_dir
     }
+
+    fn get_tombstone_retention_days(&self) -> i64 {
+        self.tombstone_retention_days
+            .unwrap_or(hypertask_task_io_operations::DEFAULT_TOMBSTONE_RETENTION_DAYS)
+    }
 }
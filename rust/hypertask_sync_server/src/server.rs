@@ -1,8 +1,11 @@
 use crate::cli_args::CliArgs;
 use crate::sync_secret;
+use chrono::prelude::*;
 use futures::future::BoxFuture;
 use hypertask_engine::prelude::*;
-use hypertask_task_io_operations::{delete_task, get_input_tasks, get_task, put_task};
+use hypertask_task_io_operations::{
+    delete_task, get_stored_tasks, get_task, put_task, remove_expired_tombstones,
+};
 use std::collections::HashMap;
 use std::rc::Rc;
 
@@ -60,7 +63,7 @@ fn attach_get_hashes(app: &mut ServerWithState) {
 
             let mut task_hashes = TaskHashes::new();
             let input_tasks: HashMap<Rc<Id>, Rc<Task>> =
-                get_input_tasks(config).expect("could not get tasks");
+                get_stored_tasks(config).expect("could not get tasks");
 
             for (id, task) in input_tasks.iter() {
                 task_hashes.insert(id.clone(), task.calculate_hash());
@@ -180,6 +183,12 @@ pub async fn start(config: CliArgs) -> HyperTaskResult<()> {
         sync_secret::generate()
     });
 
+    info!("removing expired tombstones");
+
+    if let Err(e) = remove_expired_tombstones(&config, &Utc::now()) {
+        error!("could not remove expired tombstones: {}", e);
+    }
+
     let mut app = tide::with_state((config, secret.clone()));
 
     app.middleware(AuthMiddleware());
@@ -2,9 +2,12 @@
 extern crate log;
 extern crate hypertask_engine;
 
+use chrono::prelude::*;
 use hypertask_engine::prelude::*;
 use hypertask_task_io_operations::ProvidesDataDir;
-use hypertask_task_io_operations::{delete_task, get_input_tasks, get_task, put_task};
+use hypertask_task_io_operations::{
+    delete_task, get_stored_tasks, get_task, put_task, remove_expired_tombstones,
+};
 use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
@@ -20,7 +23,7 @@ pub fn get_local_task_hash_map<Config: ProvidesDataDir>(
 ) -> HyperTaskResult<TaskHashes> {
     let mut task_hashes = TaskHashes::new();
 
-    let input_tasks: HashMap<Rc<Id>, Rc<Task>> = get_input_tasks(config)?;
+    let input_tasks: HashMap<Rc<Id>, Rc<Task>> = get_stored_tasks(config)?;
 
     for (id, task) in input_tasks.iter() {
         task_hashes.insert(id.clone(), task.calculate_hash());
@@ -149,5 +152,9 @@ pub async fn sync_all_tasks_async<Config: ProvidesDataDir + ProvidesServerDetail
         }
     }
 
+    info!("removing expired tombstones");
+
+    remove_expired_tombstones(config, &Utc::now())?;
+
     Ok(())
 }
@@ -85,8 +85,20 @@ pub fn get_task<Config: ProvidesDataDir>(
     Ok(Some(task))
 }
 
+/// Gets every task that should be passed to the engine, tombstones are left out
 pub fn get_input_tasks<Config: ProvidesDataDir>(
     config: &Config,
+) -> HyperTaskResult<HashMap<Rc<Id>, Rc<Task>>> {
+    let mut map = get_stored_tasks(config)?;
+
+    map.retain(|_, task| !task.is_tombstone());
+
+    Ok(map)
+}
+
+/// Gets every task in the data dir, including tombstones for deleted tasks
+pub fn get_stored_tasks<Config: ProvidesDataDir>(
+    config: &Config,
 ) -> HyperTaskResult<HashMap<Rc<Id>, Rc<Task>>> {
     let task_state_dir: &PathBuf = config.get_task_state_dir();
     let task_files_iterator = fs::read_dir(&task_state_dir).map_err(|e| {
@@ -109,6 +121,15 @@ pub fn get_input_tasks<Config: ProvidesDataDir>(
                 .from(e)
         })?;
 
+        // the journal and the working set are kept alongside the tasks as dot files
+        if task_file_path
+            .file_name()
+            .to_string_lossy()
+            .starts_with('.')
+        {
+            continue;
+        }
+
         let task_file = File::open(task_file_path.path()).map_err(|e| {
             HyperTaskError::new(HyperTaskErrorDomain::Task, HyperTaskErrorAction::Read)
                 .with_msg(|| format!("failed to open task `{:?}`", task_file_path))
@@ -0,0 +1,153 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+
+/// The state of a task before and after a command changed it, `before` is `None` if the command
+/// created the task
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JournalChange {
+    pub before: Option<Task>,
+    pub after: Task,
+}
+
+/// A record of every task that a single command changed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JournalEntry {
+    pub created_at: DateTime<Utc>,
+    pub command: String,
+    pub changes: Vec<JournalChange>,
+}
+
+fn journal_error(action: HyperTaskErrorAction, msg: &'static str) -> HyperTaskError {
+    HyperTaskError::new(HyperTaskErrorDomain::Journal, action).msg(msg)
+}
+
+/// Gets every entry in the journal, from oldest to newest
+pub fn get_journal_entries<Config: ProvidesDataDir>(
+    config: &Config,
+) -> HyperTaskResult<Vec<JournalEntry>> {
+    let file = match File::open(config.get_journal_path()) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => {
+            return Err(journal_error(HyperTaskErrorAction::Read, "could not open journal").from(e))
+        }
+    };
+
+    BufReader::new(file)
+        .lines()
+        .map(|line| {
+            let line = line.map_err(|e| {
+                journal_error(HyperTaskErrorAction::Read, "could not read journal").from(e)
+            })?;
+
+            serde_json::from_str(&line).map_err(|e| {
+                journal_error(HyperTaskErrorAction::Parse, "could not parse journal entry").from(e)
+            })
+        })
+        .collect()
+}
+
+fn put_journal_entries<Config: ProvidesDataDir>(
+    config: &Config,
+    entries: &[JournalEntry],
+) -> HyperTaskResult<()> {
+    let mut contents = String::new();
+
+    for entry in entries {
+        contents.push_str(&serde_json::to_string(entry).map_err(|e| {
+            journal_error(
+                HyperTaskErrorAction::Write,
+                "could not serialize journal entry",
+            )
+            .from(e)
+        })?);
+        contents.push('\n');
+    }
+
+    fs::write(config.get_journal_path(), contents)
+        .map_err(|e| journal_error(HyperTaskErrorAction::Write, "could not write journal").from(e))
+}
+
+/// Adds an entry to the end of the journal, dropping the oldest entries once there are more than
+/// the configured journal length
+pub fn append_journal_entry<Config: ProvidesDataDir>(
+    config: &Config,
+    entry: &JournalEntry,
+) -> HyperTaskResult<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(config.get_journal_path())
+        .map_err(|e| {
+            journal_error(HyperTaskErrorAction::Write, "could not open journal").from(e)
+        })?;
+
+    let line = serde_json::to_string(entry).map_err(|e| {
+        journal_error(
+            HyperTaskErrorAction::Write,
+            "could not serialize journal entry",
+        )
+        .from(e)
+    })?;
+
+    writeln!(file, "{}", line).map_err(|e| {
+        journal_error(HyperTaskErrorAction::Write, "could not write journal").from(e)
+    })?;
+
+    // trimmed line by line, rather than by parsing each entry, so that a bad line in the journal
+    // can't stop new entries from being added
+    let contents = fs::read_to_string(config.get_journal_path())
+        .map_err(|e| journal_error(HyperTaskErrorAction::Read, "could not read journal").from(e))?;
+    let lines: Vec<&str> = contents.lines().filter(|line| !line.is_empty()).collect();
+    let journal_length = config.get_journal_length();
+
+    if lines.len() > journal_length {
+        let mut trimmed = lines[lines.len() - journal_length..].join("\n");
+        trimmed.push('\n');
+
+        fs::write(config.get_journal_path(), trimmed).map_err(|e| {
+            journal_error(HyperTaskErrorAction::Write, "could not write journal").from(e)
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Gets the newest `count` entries from the journal, from newest to oldest, without removing them
+pub fn get_latest_journal_entries<Config: ProvidesDataDir>(
+    config: &Config,
+    count: usize,
+) -> HyperTaskResult<Vec<JournalEntry>> {
+    let mut entries = get_journal_entries(config)?;
+
+    if count > entries.len() {
+        return Err(
+            HyperTaskError::new(HyperTaskErrorDomain::Journal, HyperTaskErrorAction::Read)
+                .with_msg(|| {
+                    format!(
+                        "can't undo {} commands, the journal only has {}",
+                        count,
+                        entries.len()
+                    )
+                }),
+        );
+    }
+
+    let mut latest = entries.split_off(entries.len() - count);
+    latest.reverse();
+
+    Ok(latest)
+}
+
+/// Removes the newest entry from the journal, once the command it records has been undone
+pub fn remove_latest_journal_entry<Config: ProvidesDataDir>(
+    config: &Config,
+) -> HyperTaskResult<()> {
+    let mut entries = get_journal_entries(config)?;
+
+    entries.pop();
+
+    put_journal_entries(config, &entries)
+}
@@ -1,11 +1,38 @@
+use chrono::prelude::*;
 use hypertask_engine::prelude::*;
 use serde_json;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::rc::Rc;
 
+pub const DEFAULT_TOMBSTONE_RETENTION_DAYS: i64 = 30;
+pub const DEFAULT_JOURNAL_LENGTH: usize = 100;
+
 pub trait ProvidesDataDir: Sync + Send {
     fn get_task_state_dir(&self) -> &PathBuf;
+
+    /// How long tombstones for deleted tasks are kept before they're garbage collected,
+    /// this needs to be long enough for every device to have synced the deletion
+    fn get_tombstone_retention_days(&self) -> i64 {
+        DEFAULT_TOMBSTONE_RETENTION_DAYS
+    }
+
+    /// The journal of changes lives in the task state dir as a dot file, which can't be mistaken
+    /// for a task, as ids can't start with a `.`
+    fn get_journal_path(&self) -> PathBuf {
+        self.get_task_state_dir().join(".journal")
+    }
+
+    /// The numbers given to the tasks in the last listing, this is also a dot file in the task
+    /// state dir
+    fn get_working_set_path(&self) -> PathBuf {
+        self.get_task_state_dir().join(".working_set")
+    }
+
+    /// How many commands are kept in the journal to be undone
+    fn get_journal_length(&self) -> usize {
+        DEFAULT_JOURNAL_LENGTH
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -17,3 +44,30 @@ pub use wasm::*;
 mod cli;
 #[cfg(not(target_arch = "wasm32"))]
 pub use cli::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod journal;
+#[cfg(not(target_arch = "wasm32"))]
+pub use journal::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod working_set;
+#[cfg(not(target_arch = "wasm32"))]
+pub use working_set::*;
+
+pub fn remove_expired_tombstones<Config: ProvidesDataDir>(
+    config: &Config,
+    now: &DateTime<Utc>,
+) -> HyperTaskResult<()> {
+    let retention = time::Duration::days(config.get_tombstone_retention_days());
+
+    for (id, task) in get_stored_tasks(config)?.iter() {
+        if let Some(deleted_at) = task.get_deleted_at() {
+            if *deleted_at + retention < *now {
+                delete_task(config, id)?;
+            }
+        }
+    }
+
+    Ok(())
+}
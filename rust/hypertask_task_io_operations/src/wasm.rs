@@ -75,6 +75,14 @@ pub fn get_task<Config>(_: &Config, id: &Id) -> HyperTaskResult<Option<Task>> {
 }
 
 pub fn get_input_tasks<Config>(config: &Config) -> HyperTaskResult<HashMap<Rc<Id>, Rc<Task>>> {
+    let mut tasks = get_stored_tasks(config)?;
+
+    tasks.retain(|_, task| !task.is_tombstone());
+
+    Ok(tasks)
+}
+
+pub fn get_stored_tasks<Config>(config: &Config) -> HyperTaskResult<HashMap<Rc<Id>, Rc<Task>>> {
     let local_storage = get_local_storage()?;
 
     let mut tasks = HashMap::new();
@@ -0,0 +1,55 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, ErrorKind};
+
+/// A task in the last listing, along with when it was last updated, so a number can be checked
+/// to still refer to the task that was shown
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WorkingSetEntry {
+    pub id: Id,
+    pub updated_at: DateTime<Utc>,
+}
+
+fn working_set_error(action: HyperTaskErrorAction, msg: &'static str) -> HyperTaskError {
+    HyperTaskError::new(HyperTaskErrorDomain::Task, action).msg(msg)
+}
+
+/// Gets the tasks in the last listing in the order they were shown, task `1` is first
+pub fn get_working_set<Config: ProvidesDataDir>(
+    config: &Config,
+) -> HyperTaskResult<Vec<WorkingSetEntry>> {
+    let file = match File::open(config.get_working_set_path()) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => {
+            return Err(working_set_error(
+                HyperTaskErrorAction::Read,
+                "could not open working set",
+            )
+            .from(e))
+        }
+    };
+
+    serde_json::from_reader(BufReader::new(file)).map_err(|e| {
+        working_set_error(HyperTaskErrorAction::Parse, "could not parse working set").from(e)
+    })
+}
+
+/// Replaces the working set with the tasks of a new listing
+pub fn put_working_set<Config: ProvidesDataDir>(
+    config: &Config,
+    entries: &[WorkingSetEntry],
+) -> HyperTaskResult<()> {
+    let contents = serde_json::to_string(entries).map_err(|e| {
+        working_set_error(
+            HyperTaskErrorAction::Write,
+            "could not serialize working set",
+        )
+        .from(e)
+    })?;
+
+    fs::write(config.get_working_set_path(), contents).map_err(|e| {
+        working_set_error(HyperTaskErrorAction::Write, "could not write working set").from(e)
+    })
+}